@@ -0,0 +1,72 @@
+//! Prints a capture made by a `Recorder` as Modbus requests and responses.
+//!
+//! Usage: embedded-aim-motor-example-decode-capture [--tcp] [--gap <us>] <capture file>
+
+use embedded_aim_motor::{CaptureReader, DecodedFrame, Protocol, RecordKind};
+
+struct Frame {
+    kind: RecordKind,
+    // Time since the start of the capture of the first byte of the frame
+    time: u64,
+    data: Vec<u8>,
+}
+
+fn main() {
+    let mut protocol = Protocol::Rtu;
+    // Records of the same kind further apart than this are treated as separate frames
+    let mut gap = 3000;
+    let mut path = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tcp" => protocol = Protocol::Tcp,
+            "--gap" => {
+                gap = args
+                    .next()
+                    .and_then(|gap| gap.parse().ok())
+                    .expect("--gap needs a time in microseconds")
+            }
+            _ => path = Some(arg),
+        }
+    }
+
+    let path = path.expect("no capture file given");
+    let capture = std::fs::read(&path).expect("failed to read capture");
+
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut time = 0;
+
+    let mut records = CaptureReader::new(&capture);
+    for record in records.by_ref() {
+        let delay = record.delay.as_micros();
+        time += delay;
+
+        match frames.last_mut() {
+            Some(frame) if frame.kind == record.kind && delay < gap => {
+                frame.data.extend_from_slice(record.data)
+            }
+            _ => frames.push(Frame {
+                kind: record.kind,
+                time,
+                data: record.data.to_vec(),
+            }),
+        }
+    }
+
+    let mut previous = 0;
+    for frame in &frames {
+        println!(
+            "{:>10.3} ms (+{:>8.3}) {}",
+            frame.time as f64 / 1000.,
+            (frame.time - previous) as f64 / 1000.,
+            DecodedFrame::new(frame.kind, &frame.data, protocol)
+        );
+        previous = frame.time;
+    }
+
+    let remaining = records.remaining().len();
+    if remaining > 0 {
+        eprintln!("{remaining} bytes at the end of the capture could not be decoded");
+    }
+}
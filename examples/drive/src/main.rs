@@ -1,18 +1,18 @@
-//! Demonstrates driving the motor by absolute position in position mode.
+//! Demonstrates driving the motor by absolute position in position mode, following a planned
+//! trajectory.
 
 #![no_std]
 #![no_main]
 
-use defmt::{debug, info};
+use defmt::info;
 use embassy_executor::Spawner;
-use embassy_futures::select::{Either, select};
 use embassy_rp::{
     bind_interrupts,
     peripherals::UART0,
     uart::{BufferedInterruptHandler, BufferedUart, Config, DataBits, Parity, StopBits},
 };
-use embassy_time::{Duration, Ticker};
-use embedded_aim_motor::{Motor, RtuBaud};
+use embassy_time::{Duration, Timer};
+use embedded_aim_motor::{Motor, RtuBaud, SetpointCommand, Trajectory, TrajectoryLimits};
 use portable_atomic as _;
 use static_cell::StaticCell;
 use {defmt_rtt as _, panic_probe as _};
@@ -57,33 +57,41 @@ async fn main(_spawner: Spawner) {
     motor.set_acceleration(3000).await.unwrap();
     motor.set_parameter_save_flag(true).await.unwrap();
 
-    let mut position = 0u32;
+    let limits = TrajectoryLimits {
+        max_velocity: 20_000.,
+        max_acceleration: 50_000.,
+        max_jerk: Some(500_000.),
+    };
 
-    let mut motor_tick = Ticker::every(Duration::from_millis(20));
-    let mut pos_tick = Ticker::every(Duration::from_millis(500));
+    let mut position = 0u32;
 
     loop {
-        match select(motor_tick.next(), pos_tick.next()).await {
-            Either::First(_) => {
-                let start = embassy_time::Instant::now();
-                let _ = motor.set_absolute_position_custom(position).await;
-                let end = embassy_time::Instant::now();
-
-                position = position.saturating_add(1000);
-
-                let delta = end - start;
-                debug!("delta = {}ms", delta.as_millis());
-            }
-            Either::Second(_) => {
-                if let Ok(actual) = motor.absolute_position().await {
-                    info!(
-                        "pos req/act: {}/{} (diff {})",
-                        position,
-                        actual,
-                        position - actual
-                    );
-                }
-            }
+        let target = position.saturating_add(100_000);
+        let trajectory = Trajectory::new(position, target, &limits).unwrap();
+        info!(
+            "Moving to {} over {}ms",
+            target,
+            trajectory.duration().as_millis()
+        );
+
+        match motor
+            .follow_trajectory(
+                &trajectory,
+                SetpointCommand::AbsolutePosition,
+                Duration::from_millis(20),
+                25,
+            )
+            .await
+        {
+            Ok(report) => info!("Tracking: {}", report),
+            Err(e) => info!("Trajectory failed: {}", e),
         }
+
+        motor.timing().log();
+        motor.reset_timing();
+
+        position = target;
+
+        Timer::after_secs(1).await;
     }
 }
@@ -1,10 +1,15 @@
-//! This *should* demonstrate settime the baud rate used for Modbus communication.
-//! It does not, despite doing what is described in the data sheet....
+//! Attempts to set the baud rate used for Modbus communication.
+//!
+//! This follows the sequence described in the data sheet, which has not yet been seen to change
+//! the baud rate of a real motor.
+//! The written values are read back before they are saved, so a motor that ignores them is
+//! reported rather than waited for.
+//! The motor must be power cycled after the new baud rate has been saved.
 
 #![no_std]
 #![no_main]
 
-use defmt::{info, warn};
+use defmt::{error, info, warn};
 use embassy_executor::Spawner;
 use embassy_rp::{
     bind_interrupts,
@@ -12,7 +17,7 @@ use embassy_rp::{
     uart::{BufferedInterruptHandler, BufferedUart, Config, DataBits, Parity, StopBits},
 };
 use embassy_time::{Duration, Timer};
-use embedded_aim_motor::{Motor, RtuBaud};
+use embedded_aim_motor::{BaudRateAdapter, Error, Motor, RtuBaud, SerialConfig};
 use portable_atomic as _;
 use static_cell::StaticCell;
 use {defmt_rtt as _, panic_probe as _};
@@ -42,31 +47,37 @@ async fn main(_spawner: Spawner) {
     static RX_BUFFER: StaticCell<[u8; RX_BUFFER_SIZE]> = StaticCell::new();
     let rx_buf = &mut RX_BUFFER.init([0; RX_BUFFER_SIZE])[..];
 
-    let mut uart = BufferedUart::new(p.UART0, p.PIN_0, p.PIN_1, Irqs, tx_buf, rx_buf, config);
-
-    let original_baud = RtuBaud::Baud19200;
-    let new_baud = RtuBaud::Baud115200;
+    let uart = BufferedUart::new(p.UART0, p.PIN_0, p.PIN_1, Irqs, tx_buf, rx_buf, config);
+    // Only the baud rate can be changed at runtime, the motor is always 8N1
+    let uart = BaudRateAdapter::new(uart, |uart: &mut BufferedUart, config: &SerialConfig| {
+        if *config != SerialConfig::new(config.baud) {
+            return Err(Error::InvalidArgument);
+        }
+        uart.set_baudrate(config.baud);
+        Ok(())
+    });
 
-    let mut motor = Motor::new(&mut uart, original_baud, 0x01, Duration::from_millis(50));
+    let mut motor = Motor::new(uart, RtuBaud::Baud19200, 0x01, Duration::from_millis(50));
     info!("Device address: {}", motor.device_address().await);
 
-    motor.set_baud_rate(new_baud.clone()).await.unwrap();
+    let baud = RtuBaud::Baud115200;
 
-    info!("Power cycle the motor now, plz...");
-    Timer::after_secs(1).await;
+    if let Err(e) = motor.set_baud_rate(baud).await {
+        error!("Saving the baud rate failed: {}", e);
+        return;
+    }
 
-    uart.set_baudrate(115200);
-    let mut motor = Motor::new(&mut uart, new_baud, Duration::from_millis(50));
+    info!("Power cycle the motor now, plz...");
 
     loop {
-        match motor.device_address().await {
-            Ok(addr) => {
+        match motor.switch_baud(baud, Duration::from_secs(30)).await {
+            Ok(()) => {
                 info!("I see the motor again, yey!");
-                info!("Device address: {}", addr);
+                info!("Device address: {}", motor.device_address().await);
                 break;
             }
             Err(e) => {
-                warn!("Motor communication failed: {}", e);
+                warn!("Motor not reachable at the new baud rate: {}", e);
                 Timer::after_millis(500).await;
             }
         }
@@ -0,0 +1,224 @@
+//! Runs a `Motor` over TCP against a local stand-in for a serial to Ethernet gateway.
+//!
+//! The stand-in serves a single simulated motor, in both Modbus TCP and RTU over TCP modes.
+//! Before some responses it sends a frame for another protocol, and a late response to an earlier
+//! transaction, both of which must be skipped.
+//!
+//! Exits with an error if any response does not match what the simulated motor holds.
+
+use embassy_time::{Duration, Timer};
+use embedded_aim_motor::{Motor, Protocol};
+use std::{
+    io::{ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+};
+
+const UNIT_ID: u8 = 1;
+
+fn main() {
+    for protocol in [Protocol::Tcp, Protocol::RtuOverTcp] {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+        let address = listener.local_addr().unwrap();
+        let gateway = thread::spawn(move || gateway(listener, protocol));
+
+        let stream = TcpStream::connect(address).expect("failed to connect");
+        stream.set_nonblocking(true).unwrap();
+
+        embassy_futures::block_on(run(Stream(stream), protocol));
+
+        gateway.join().unwrap();
+        println!("{protocol:?}: ok");
+    }
+}
+
+async fn run(stream: Stream, protocol: Protocol) {
+    let timeout = Duration::from_millis(500);
+    let mut motor = match protocol {
+        Protocol::Tcp => Motor::new_tcp(stream, UNIT_ID, timeout),
+        _ => Motor::new_rtu_over_tcp(stream, UNIT_ID, timeout),
+    };
+
+    assert_eq!(motor.device_address().await, Ok(UNIT_ID as u16));
+
+    for rpm in [100, 2500] {
+        motor.set_target_rpm(rpm).await.unwrap();
+        assert_eq!(motor.target_rpm().await, Ok(rpm));
+    }
+
+    motor.set_target_position(0x0012_3456).await.unwrap();
+    assert_eq!(motor.target_position().await, Ok(0x0012_3456));
+
+    let status = motor.status().await.unwrap();
+    assert_eq!(status.temperature, 31);
+}
+
+/// A TCP stream for the `Motor`, polled so that timeouts work on a single threaded executor.
+struct Stream(TcpStream);
+
+impl embedded_io_async::ErrorType for Stream {
+    type Error = embedded_io_async::ErrorKind;
+}
+
+impl embedded_io_async::Read for Stream {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        loop {
+            match self.0.read(buf) {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => Timer::after_micros(100).await,
+                result => return result.map_err(|_| embedded_io_async::ErrorKind::Other),
+            }
+        }
+    }
+}
+
+impl embedded_io_async::Write for Stream {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.0
+            .write(buf)
+            .map_err(|_| embedded_io_async::ErrorKind::Other)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.0
+            .flush()
+            .map_err(|_| embedded_io_async::ErrorKind::Other)
+    }
+}
+
+/// Serves one connection, answering requests from a simulated motor.
+fn gateway(listener: TcpListener, protocol: Protocol) {
+    let (mut connection, _) = listener.accept().expect("failed to accept");
+
+    let mut registers = [0u16; 0x1A];
+    registers[0x12] = 31;
+    registers[0x15] = UNIT_ID as u16;
+
+    let mut received = Vec::new();
+    let mut previous: Option<Vec<u8>> = None;
+    let mut count = 0;
+
+    loop {
+        let mut buf = [0u8; 256];
+        match connection.read(&mut buf) {
+            Ok(0) | Err(_) => return,
+            Ok(n) => received.extend_from_slice(&buf[..n]),
+        }
+
+        let Some((header, pdu, len)) = take_request(&received, protocol) else {
+            continue;
+        };
+        let response = respond(&mut registers, &pdu);
+        received.drain(..len);
+        count += 1;
+
+        let frame = match protocol {
+            Protocol::Tcp => {
+                let mut frame = header[..4].to_vec();
+                frame.extend_from_slice(&(response.len() as u16 + 1).to_be_bytes());
+                frame.push(UNIT_ID);
+                frame.extend_from_slice(&response);
+                frame
+            }
+            _ => {
+                let mut frame = vec![UNIT_ID];
+                frame.extend_from_slice(&response);
+                frame.extend_from_slice(&crc16(&frame).to_le_bytes());
+                frame
+            }
+        };
+
+        let mut out = Vec::new();
+        if protocol == Protocol::Tcp && count % 2 == 0 {
+            // A frame with the right transaction ID for another protocol
+            out.extend_from_slice(&header[..2]);
+            out.extend_from_slice(&[0, 1, 0, 3, UNIT_ID, 0x83, 0x02]);
+        }
+        if count % 3 == 0
+            && let Some(previous) = &previous
+        {
+            out.extend_from_slice(previous);
+        }
+        out.extend_from_slice(&frame);
+
+        // Send in small pieces, as a gateway forwarding a serial line would
+        for chunk in out.chunks(5) {
+            connection.write_all(chunk).unwrap();
+            thread::sleep(std::time::Duration::from_micros(200));
+        }
+
+        // Late responses are only distinguished by transaction ID in Modbus TCP
+        if protocol == Protocol::Tcp {
+            previous = Some(frame);
+        }
+    }
+}
+
+/// Gets the header and PDU of the next complete request, and its length
+fn take_request(received: &[u8], protocol: Protocol) -> Option<(Vec<u8>, Vec<u8>, usize)> {
+    match protocol {
+        Protocol::Tcp => {
+            let header = received.get(..7)?;
+            let len = 6 + u16::from_be_bytes([header[4], header[5]]) as usize;
+            let pdu = received.get(7..len)?;
+            Some((header.to_vec(), pdu.to_vec(), len))
+        }
+        _ => {
+            // Requests are all either 8 bytes, or a write of multiple registers
+            let len = match received.get(1)? {
+                0x10 => 9 + *received.get(6)? as usize,
+                _ => 8,
+            };
+            let frame = received.get(..len)?;
+            assert_eq!(
+                crc16(&frame[..len - 2]).to_le_bytes(),
+                frame[len - 2..],
+                "bad CRC"
+            );
+            Some((frame[..1].to_vec(), frame[1..len - 2].to_vec(), len))
+        }
+    }
+}
+
+/// Applies a request PDU to the registers, returning the response PDU
+fn respond(registers: &mut [u16], pdu: &[u8]) -> Vec<u8> {
+    let word = |i: usize| u16::from_be_bytes([pdu[i], pdu[i + 1]]);
+    let start = word(1) as usize;
+
+    match pdu[0] {
+        0x03 => {
+            let count = word(3) as usize;
+            let mut response = vec![0x03, (count * 2) as u8];
+            for value in &registers[start..start + count] {
+                response.extend_from_slice(&value.to_be_bytes());
+            }
+            response
+        }
+        0x06 => {
+            registers[start] = word(3);
+            pdu.to_vec()
+        }
+        0x10 => {
+            let count = word(3) as usize;
+            for i in 0..count {
+                registers[start + i] = word(6 + i * 2);
+            }
+            pdu[..5].to_vec()
+        }
+        function => vec![function | 0x80, 0x01],
+    }
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFF;
+    for byte in data {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
@@ -0,0 +1,694 @@
+use crate::{Protocol, Result, SerialConfig, SetBaudRate};
+use core::fmt;
+use embassy_time::{Duration, Instant, Timer};
+use modbus_core::{Data, ExceptionResponse, Request, Response, rtu::crc16};
+
+// Each record is a header followed by the data:
+// - kind, 0 for bytes sent and 1 for bytes received
+// - time since the previous record in microseconds, u32 little endian, saturating
+// - length of the data, u16 little endian
+const HEADER_LEN: usize = 7;
+
+/// Direction of the bytes in a [`Record`].
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordKind {
+    /// Sent to the bus
+    Tx,
+
+    /// Received from the bus
+    Rx,
+}
+
+/// The bytes passed in a single read or write of a [`Recorder`].
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Record<'a> {
+    pub kind: RecordKind,
+
+    /// Time since the previous record, or since the recording started for the first record
+    pub delay: Duration,
+
+    pub data: &'a [u8],
+}
+
+impl<'a> Record<'a> {
+    /// Number of bytes the record takes in a capture
+    pub fn encoded_len(&self) -> usize {
+        HEADER_LEN + self.data.len()
+    }
+
+    fn header(&self) -> [u8; HEADER_LEN] {
+        let kind = match self.kind {
+            RecordKind::Tx => 0,
+            RecordKind::Rx => 1,
+        };
+        let delay = u32::try_from(self.delay.as_micros()).unwrap_or(u32::MAX);
+        let len = self.data.len() as u16;
+
+        let mut header = [0; HEADER_LEN];
+        header[0] = kind;
+        header[1..5].copy_from_slice(&delay.to_le_bytes());
+        header[5..].copy_from_slice(&len.to_le_bytes());
+        header
+    }
+
+    fn decode(capture: &'a [u8]) -> Option<(Self, &'a [u8])> {
+        let header = capture.get(..HEADER_LEN)?;
+
+        let kind = match header[0] {
+            0 => RecordKind::Tx,
+            1 => RecordKind::Rx,
+            _ => return None,
+        };
+        let delay = u32::from_le_bytes([header[1], header[2], header[3], header[4]]);
+        let len = u16::from_le_bytes([header[5], header[6]]) as usize;
+
+        let rest = &capture[HEADER_LEN..];
+        if rest.len() < len {
+            return None;
+        }
+        let (data, rest) = rest.split_at(len);
+
+        Some((
+            Self {
+                kind,
+                delay: Duration::from_micros(delay as u64),
+                data,
+            },
+            rest,
+        ))
+    }
+}
+
+/// Somewhere for a [`Recorder`] to store the records it captures.
+pub trait CaptureSink {
+    fn record(&mut self, record: &Record);
+}
+
+impl<T: CaptureSink + ?Sized> CaptureSink for &mut T {
+    fn record(&mut self, record: &Record) {
+        T::record(self, record)
+    }
+}
+
+/// Keeps the most recent records in a ring buffer of `N` bytes.
+///
+/// The oldest records are dropped to make room for new ones, so that the buffer always holds the
+/// traffic leading up to a fault.
+pub struct CaptureBuffer<const N: usize> {
+    buffer: [u8; N],
+    start: usize,
+    len: usize,
+    dropped: u32,
+}
+
+impl<const N: usize> Default for CaptureBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> CaptureBuffer<N> {
+    pub const fn new() -> Self {
+        Self {
+            buffer: [0; N],
+            start: 0,
+            len: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Number of bytes of the capture held
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of records dropped to make room for newer ones, or because they were larger than the
+    /// buffer
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.len = 0;
+        self.dropped = 0;
+    }
+
+    /// The capture, oldest record first, split in two where it wraps around the end of the buffer
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        let end = self.start + self.len;
+        if end <= N {
+            (&self.buffer[self.start..end], &[])
+        } else {
+            (&self.buffer[self.start..], &self.buffer[..end - N])
+        }
+    }
+
+    /// Writes the capture to a sink, e.g. a spare UART or flash, for decoding on a host
+    pub async fn write_to<W: embedded_io_async::Write>(
+        &self,
+        mut sink: W,
+    ) -> core::result::Result<(), W::Error> {
+        let (a, b) = self.as_slices();
+        sink.write_all(a).await?;
+        sink.write_all(b).await?;
+        sink.flush().await
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.buffer[(self.start + self.len) % N] = b;
+            self.len += 1;
+        }
+    }
+
+    fn drop_oldest(&mut self) {
+        let byte = |i: usize| self.buffer[(self.start + i) % N];
+        let len = HEADER_LEN + u16::from_le_bytes([byte(5), byte(6)]) as usize;
+
+        self.start = (self.start + len) % N;
+        self.len -= len;
+        self.dropped += 1;
+    }
+}
+
+impl<const N: usize> CaptureSink for CaptureBuffer<N> {
+    fn record(&mut self, record: &Record) {
+        let len = record.encoded_len();
+        if len > N {
+            self.dropped += 1;
+            return;
+        }
+
+        while N - self.len < len {
+            self.drop_oldest();
+        }
+
+        self.push(&record.header());
+        self.push(record.data);
+    }
+}
+
+/// Wraps a transport, recording every byte sent and received.
+///
+/// Records are written to the [`CaptureSink`] as they happen, see [`CaptureReader`] and
+/// [`DecodedFrame`] for decoding them and [`Replay`] for feeding them back into a
+/// [`Motor`](crate::Motor).
+pub struct Recorder<I, S> {
+    inner: I,
+    sink: S,
+    last: Instant,
+}
+
+impl<I, S: CaptureSink> Recorder<I, S> {
+    pub fn new(inner: I, sink: S) -> Self {
+        Self {
+            inner,
+            sink,
+            last: Instant::now(),
+        }
+    }
+
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    pub fn sink_mut(&mut self) -> &mut S {
+        &mut self.sink
+    }
+
+    pub fn into_inner(self) -> (I, S) {
+        (self.inner, self.sink)
+    }
+
+    fn record(&mut self, kind: RecordKind, data: &[u8]) {
+        let now = Instant::now();
+        let mut delay = now - self.last;
+        self.last = now;
+
+        for data in data.chunks(u16::MAX as usize) {
+            self.sink.record(&Record { kind, delay, data });
+            delay = Duration::from_ticks(0);
+        }
+    }
+}
+
+impl<I: SetBaudRate, S> SetBaudRate for Recorder<I, S> {
+    fn set_baud_rate(&mut self, baud: u32) -> Result<()> {
+        self.inner.set_baud_rate(baud)
+    }
+
+    fn set_serial_config(&mut self, config: &SerialConfig) -> Result<()> {
+        self.inner.set_serial_config(config)
+    }
+}
+
+impl<I: embedded_io_async::ErrorType, S> embedded_io_async::ErrorType for Recorder<I, S> {
+    type Error = I::Error;
+}
+
+impl<I: embedded_io_async::Read, S: CaptureSink> embedded_io_async::Read for Recorder<I, S> {
+    async fn read(&mut self, buf: &mut [u8]) -> core::result::Result<usize, Self::Error> {
+        let n = self.inner.read(buf).await?;
+        if n > 0 {
+            self.record(RecordKind::Rx, &buf[..n]);
+        }
+        Ok(n)
+    }
+}
+
+impl<I: embedded_io_async::Write, S: CaptureSink> embedded_io_async::Write for Recorder<I, S> {
+    async fn write(&mut self, buf: &[u8]) -> core::result::Result<usize, Self::Error> {
+        let n = self.inner.write(buf).await?;
+        if n > 0 {
+            self.record(RecordKind::Tx, &buf[..n]);
+        }
+        Ok(n)
+    }
+
+    async fn flush(&mut self) -> core::result::Result<(), Self::Error> {
+        self.inner.flush().await
+    }
+}
+
+/// Iterates over the records of a capture.
+///
+/// Iteration stops at the first record that is incomplete or invalid.
+#[derive(Debug, Clone)]
+pub struct CaptureReader<'a> {
+    capture: &'a [u8],
+}
+
+impl<'a> CaptureReader<'a> {
+    pub fn new(capture: &'a [u8]) -> Self {
+        Self { capture }
+    }
+
+    /// The part of the capture that has not been read, not empty if the capture was truncated
+    pub fn remaining(&self) -> &'a [u8] {
+        self.capture
+    }
+}
+
+impl<'a> Iterator for CaptureReader<'a> {
+    type Item = Record<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (record, rest) = Record::decode(self.capture)?;
+        self.capture = rest;
+        Some(record)
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayError {
+    #[error("Request differs from the capture")]
+    Diverged,
+
+    #[error("End of the capture")]
+    EndOfCapture,
+}
+
+impl embedded_io_async::Error for ReplayError {
+    fn kind(&self) -> embedded_io_async::ErrorKind {
+        embedded_io_async::ErrorKind::Other
+    }
+}
+
+/// A transport that plays back a capture made by a [`Recorder`].
+///
+/// Received bytes are returned in the same chunks, and by default with the same delays, as they
+/// were recorded, so that a [`Motor`](crate::Motor) sees exactly the same traffic.
+/// Delays are kept relative to when the replay was created, so a consumer that falls behind
+/// catches up rather than the delays adding up.
+/// Bytes written must match those that were recorded, otherwise [`ReplayError::Diverged`] is
+/// returned.
+/// Reads wait forever when the capture has no more bytes to receive before the next request,
+/// like a motor that does not respond.
+pub struct Replay<'a> {
+    records: CaptureReader<'a>,
+    current: Option<Record<'a>>,
+    offset: usize,
+    realtime: bool,
+
+    started: Instant,
+    // Time from the start of the replay to the current record
+    due: Duration,
+}
+
+impl<'a> Replay<'a> {
+    pub fn new(capture: &'a [u8]) -> Self {
+        let mut records = CaptureReader::new(capture);
+        let current = records.next();
+
+        Self {
+            current,
+            records,
+            offset: 0,
+            realtime: true,
+            started: Instant::now(),
+            due: current.map(|r| r.delay).unwrap_or_default(),
+        }
+    }
+
+    /// Sets if received bytes are delayed by the time recorded before them, on by default
+    ///
+    /// Without the delays a replay runs as fast as possible, but responses that arrived after a
+    /// timeout, or gaps within a response, are not reproduced.
+    pub fn set_realtime(&mut self, realtime: bool) {
+        self.realtime = realtime;
+    }
+
+    /// If every record of the capture has been replayed
+    pub fn is_finished(&self) -> bool {
+        self.current.is_none()
+    }
+
+    fn consume(&mut self, record: &Record, n: usize) {
+        self.offset += n;
+        if self.offset == record.data.len() {
+            self.current = self.records.next();
+            self.offset = 0;
+            if let Some(next) = self.current {
+                self.due += next.delay;
+            }
+        }
+    }
+}
+
+impl SetBaudRate for Replay<'_> {
+    fn set_baud_rate(&mut self, _baud: u32) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_serial_config(&mut self, _config: &SerialConfig) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl embedded_io_async::ErrorType for Replay<'_> {
+    type Error = ReplayError;
+}
+
+impl embedded_io_async::Read for Replay<'_> {
+    async fn read(&mut self, buf: &mut [u8]) -> core::result::Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let record = match self.current {
+            Some(record) if record.kind == RecordKind::Rx => record,
+            _ => core::future::pending().await,
+        };
+
+        if self.realtime && self.offset == 0 {
+            Timer::at(self.started + self.due).await;
+        }
+
+        let data = &record.data[self.offset..];
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        self.consume(&record, n);
+
+        Ok(n)
+    }
+}
+
+impl embedded_io_async::Write for Replay<'_> {
+    async fn write(&mut self, buf: &[u8]) -> core::result::Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let record = match self.current {
+            Some(record) if record.kind == RecordKind::Tx => record,
+            Some(_) => return Err(ReplayError::Diverged),
+            None => return Err(ReplayError::EndOfCapture),
+        };
+
+        let expected = &record.data[self.offset..];
+        let n = expected.len().min(buf.len());
+        if buf[..n] != expected[..n] {
+            return Err(ReplayError::Diverged);
+        }
+        self.consume(&record, n);
+
+        Ok(n)
+    }
+
+    async fn flush(&mut self) -> core::result::Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Formats a frame from a capture as a Modbus request or response.
+///
+/// Records only hold the bytes of a single read or write, consecutive records of the same kind
+/// should be joined into a frame before decoding.
+pub struct DecodedFrame<'a> {
+    kind: RecordKind,
+    data: &'a [u8],
+    protocol: Protocol,
+}
+
+impl<'a> DecodedFrame<'a> {
+    pub fn new(kind: RecordKind, data: &'a [u8], protocol: Protocol) -> Self {
+        Self {
+            kind,
+            data,
+            protocol,
+        }
+    }
+
+    // The unit or slave address, and the PDU
+    fn split(&self) -> Option<(u8, &'a [u8])> {
+        let data = self.data;
+
+        match self.protocol {
+            Protocol::Rtu | Protocol::RtuOverTcp => {
+                let (adu, crc) = data.split_at_checked(data.len().checked_sub(2)?)?;
+                if adu.len() < 2 || crc16(adu) != u16::from_be_bytes([crc[0], crc[1]]) {
+                    return None;
+                }
+                Some((adu[0], &adu[1..]))
+            }
+            Protocol::Tcp => {
+                let len = u16::from_be_bytes([*data.get(4)?, *data.get(5)?]) as usize;
+                let pdu = data.get(7..6 + len)?;
+                if pdu.is_empty() || data.len() != 6 + len {
+                    return None;
+                }
+                Some((data[6], pdu))
+            }
+        }
+    }
+}
+
+impl fmt::Display for DecodedFrame<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arrow = match self.kind {
+            RecordKind::Tx => "->",
+            RecordKind::Rx => "<-",
+        };
+
+        let Some((unit, pdu)) = self.split() else {
+            write!(f, "{arrow} invalid frame")?;
+            return write_hex(f, self.data);
+        };
+        write!(f, "{arrow} {unit:3}: ")?;
+
+        match self.kind {
+            RecordKind::Tx => match Request::try_from(pdu) {
+                Ok(Request::ReadHoldingRegisters(address, count)) => {
+                    write!(f, "read {count} from {address:#06x}")
+                }
+                Ok(Request::WriteSingleRegister(address, value)) => {
+                    write!(f, "write {address:#06x} = {value:#06x}")
+                }
+                Ok(Request::WriteMultipleRegisters(address, data)) => {
+                    write!(f, "write {address:#06x} =")?;
+                    write_words(f, &data)
+                }
+                Ok(Request::Custom(function, data)) => {
+                    write!(f, "custom {:#04x}", function.value())?;
+                    write_hex(f, data)
+                }
+                Ok(request) => write!(f, "{request:?}"),
+                Err(_) => {
+                    write!(f, "undecodable request")?;
+                    write_hex(f, pdu)
+                }
+            },
+            RecordKind::Rx => {
+                if let Ok(exception) = ExceptionResponse::try_from(pdu) {
+                    return write!(
+                        f,
+                        "exception {:?} to {:#04x}",
+                        exception.exception,
+                        exception.function.value()
+                    );
+                }
+
+                match Response::try_from(pdu) {
+                    Ok(Response::ReadHoldingRegisters(data)) => {
+                        write!(f, "registers")?;
+                        write_words(f, &data)
+                    }
+                    Ok(Response::WriteSingleRegister(address, value)) => {
+                        write!(f, "wrote {address:#06x} = {value:#06x}")
+                    }
+                    Ok(Response::WriteMultipleRegisters(address, count)) => {
+                        write!(f, "wrote {count} from {address:#06x}")
+                    }
+                    Ok(Response::Custom(function, data)) => {
+                        write!(f, "custom {:#04x}", function.value())?;
+                        write_hex(f, data)
+                    }
+                    Ok(response) => write!(f, "{response:?}"),
+                    Err(_) => {
+                        write!(f, "undecodable response")?;
+                        write_hex(f, pdu)
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn write_words(f: &mut fmt::Formatter<'_>, data: &Data) -> fmt::Result {
+    for word in (0..data.len()).filter_map(|i| data.get(i)) {
+        write!(f, " {word:#06x}")?;
+    }
+    Ok(())
+}
+
+fn write_hex(f: &mut fmt::Formatter<'_>, data: &[u8]) -> fmt::Result {
+    for b in data {
+        write!(f, " {b:02x}")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embassy_futures::block_on;
+    use embedded_io_async::{Read, Write};
+    use std::vec::Vec;
+
+    fn record(kind: RecordKind, delay_ms: u64, data: &[u8]) -> Record<'_> {
+        Record {
+            kind,
+            delay: Duration::from_millis(delay_ms),
+            data,
+        }
+    }
+
+    fn contents<const N: usize>(buffer: &CaptureBuffer<N>) -> Vec<u8> {
+        let (a, b) = buffer.as_slices();
+        [a, b].concat()
+    }
+
+    #[test]
+    fn records_are_read_back() {
+        let mut buffer = CaptureBuffer::<64>::new();
+        buffer.record(&record(RecordKind::Tx, 1, &[1, 2, 3]));
+        buffer.record(&record(RecordKind::Rx, 2, &[4, 5]));
+
+        let capture = contents(&buffer);
+        let mut reader = CaptureReader::new(&capture);
+        assert_eq!(reader.next(), Some(record(RecordKind::Tx, 1, &[1, 2, 3])));
+        assert_eq!(reader.next(), Some(record(RecordKind::Rx, 2, &[4, 5])));
+        assert_eq!(reader.next(), None);
+        assert!(reader.remaining().is_empty());
+    }
+
+    #[test]
+    fn oldest_records_are_dropped_when_full() {
+        // Room for two records of 3 bytes, and part of a third
+        let mut buffer = CaptureBuffer::<24>::new();
+        for i in 0..5u8 {
+            buffer.record(&record(RecordKind::Rx, 0, &[i; 3]));
+        }
+
+        assert_eq!(buffer.dropped(), 3);
+        assert_eq!(buffer.len(), 20);
+
+        // The newest records wrap around the end of the buffer
+        let capture = contents(&buffer);
+        let data: Vec<_> = CaptureReader::new(&capture).map(|r| r.data[0]).collect();
+        assert_eq!(data, [3, 4]);
+    }
+
+    #[test]
+    fn record_larger_than_buffer_is_dropped() {
+        let mut buffer = CaptureBuffer::<16>::new();
+        buffer.record(&record(RecordKind::Tx, 0, &[1]));
+        buffer.record(&record(RecordKind::Rx, 0, &[0; 10]));
+
+        assert_eq!(buffer.dropped(), 1);
+        assert_eq!(CaptureReader::new(&contents(&buffer)).count(), 1);
+    }
+
+    #[test]
+    fn truncated_capture_stops_reading() {
+        let mut buffer = CaptureBuffer::<64>::new();
+        buffer.record(&record(RecordKind::Tx, 0, &[1, 2, 3]));
+        buffer.record(&record(RecordKind::Rx, 0, &[4, 5]));
+
+        let capture = contents(&buffer);
+        let mut reader = CaptureReader::new(&capture[..capture.len() - 1]);
+        assert!(reader.next().is_some());
+        assert_eq!(reader.next(), None);
+        assert_eq!(reader.remaining().len(), HEADER_LEN + 1);
+    }
+
+    #[test]
+    fn replay_diverges_from_different_request() {
+        let mut buffer = CaptureBuffer::<64>::new();
+        buffer.record(&record(RecordKind::Tx, 0, &[1, 2, 3]));
+
+        let capture = contents(&buffer);
+        let mut replay = Replay::new(&capture);
+        assert_eq!(
+            block_on(replay.write(&[1, 2, 4])),
+            Err(ReplayError::Diverged)
+        );
+    }
+
+    #[test]
+    fn replay_delays_do_not_add_up() {
+        let mut buffer = CaptureBuffer::<64>::new();
+        buffer.record(&record(RecordKind::Tx, 0, &[1]));
+        buffer.record(&record(RecordKind::Rx, 50, &[2]));
+        buffer.record(&record(RecordKind::Rx, 50, &[3]));
+
+        let capture = contents(&buffer);
+        let mut replay = Replay::new(&capture);
+        let started = Instant::now();
+
+        block_on(async {
+            replay.write_all(&[1]).await.unwrap();
+
+            // A slow consumer is already late for the first response
+            Timer::after(Duration::from_millis(100)).await;
+
+            let mut buf = [0; 2];
+            assert_eq!(replay.read(&mut buf).await, Ok(1));
+            assert_eq!(replay.read(&mut buf).await, Ok(1));
+            assert_eq!(buf[0], 3);
+        });
+
+        // Without catching up, the second response would be 50ms after the first was read
+        let elapsed = Instant::now() - started;
+        assert!(elapsed < Duration::from_millis(140), "{elapsed:?}");
+        assert!(replay.is_finished());
+    }
+}
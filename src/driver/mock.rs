@@ -0,0 +1,133 @@
+use super::MotorDriver;
+use crate::{AlarmCode, Error, MotorStatus, Result};
+
+/// A call made to a [`MockMotor`].
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DriverCall {
+    SetEnabled(bool),
+    SetVelocity(u16),
+    MoveTo(u32),
+    Position,
+    Speed,
+    Status,
+    Fault,
+}
+
+/// A drive that records the calls made to it and returns preset values, for testing motion code.
+///
+/// Up to `N` calls are recorded, later calls are counted but not recorded.
+pub struct MockMotor<const N: usize = 16> {
+    calls: [Option<DriverCall>; N],
+    count: usize,
+    fail_next: Option<Error>,
+
+    /// Value returned by [`MotorDriver::position`]
+    pub position: u32,
+
+    /// Value returned by [`MotorDriver::speed`], and in the status
+    pub speed: f32,
+
+    /// Value returned by [`MotorDriver::fault`], and in the status
+    pub fault: Option<AlarmCode>,
+
+    /// Value returned by [`MotorDriver::status`], apart from the speed and fault
+    pub status: MotorStatus,
+}
+
+impl<const N: usize> Default for MockMotor<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> MockMotor<N> {
+    pub fn new() -> Self {
+        Self {
+            calls: [None; N],
+            count: 0,
+            fail_next: None,
+            position: 0,
+            speed: 0.,
+            fault: None,
+            status: MotorStatus {
+                alarm: None,
+                current: 0.,
+                speed: 0.,
+                voltage: 0.,
+                temperature: 0,
+                pwm: 0,
+            },
+        }
+    }
+
+    /// The recorded calls, oldest first
+    pub fn calls(&self) -> impl Iterator<Item = DriverCall> + '_ {
+        self.calls.iter().flatten().copied()
+    }
+
+    /// Number of calls made, including those that were not recorded
+    pub fn call_count(&self) -> usize {
+        self.count
+    }
+
+    pub fn clear_calls(&mut self) {
+        self.calls = [None; N];
+        self.count = 0;
+    }
+
+    /// Makes the next call fail with an error, the call is still recorded
+    pub fn fail_next(&mut self, error: Error) {
+        self.fail_next = Some(error);
+    }
+
+    fn call(&mut self, call: DriverCall) -> Result<()> {
+        if let Some(slot) = self.calls.get_mut(self.count) {
+            *slot = Some(call);
+        }
+        self.count += 1;
+
+        match self.fail_next.take() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<const N: usize> MotorDriver for MockMotor<N> {
+    async fn set_enabled(&mut self, enabled: bool) -> Result<()> {
+        self.call(DriverCall::SetEnabled(enabled))
+    }
+
+    async fn set_velocity(&mut self, rpm: u16) -> Result<()> {
+        self.call(DriverCall::SetVelocity(rpm))
+    }
+
+    async fn move_to(&mut self, position: u32) -> Result<()> {
+        self.call(DriverCall::MoveTo(position))
+    }
+
+    async fn position(&mut self) -> Result<u32> {
+        self.call(DriverCall::Position)?;
+        Ok(self.position)
+    }
+
+    async fn speed(&mut self) -> Result<f32> {
+        self.call(DriverCall::Speed)?;
+        Ok(self.speed)
+    }
+
+    async fn status(&mut self) -> Result<MotorStatus> {
+        self.call(DriverCall::Status)?;
+        Ok(MotorStatus {
+            alarm: self.fault,
+            speed: self.speed,
+            ..self.status.clone()
+        })
+    }
+
+    async fn fault(&mut self) -> Result<Option<AlarmCode>> {
+        self.call(DriverCall::Fault)?;
+        Ok(self.fault)
+    }
+}
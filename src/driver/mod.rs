@@ -0,0 +1,109 @@
+mod mock;
+mod sim;
+
+pub use mock::{DriverCall, MockMotor};
+pub use sim::SimulatedMotor;
+
+use crate::{AlarmCode, Motor, MotorStatus, Result};
+
+/// How a drive follows its commands.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlMode {
+    /// Runs continuously at the commanded velocity
+    Speed,
+
+    /// Moves to the commanded position, the commanded velocity is the maximum speed
+    Position,
+}
+
+/// A servo drive that can be commanded over a bus.
+///
+/// Implemented by [`Motor`], [`SimulatedMotor`] and [`MockMotor`], so that motion code can be
+/// generic over the drive and run without hardware.
+/// The control mode is configured on the drive itself and is not changed through this trait.
+pub trait MotorDriver {
+    fn set_enabled(&mut self, enabled: bool) -> impl Future<Output = Result<()>>;
+
+    /// Sets the velocity in RPM
+    ///
+    /// In [`ControlMode::Speed`] this is the target speed, in [`ControlMode::Position`] it is the
+    /// maximum speed.
+    fn set_velocity(&mut self, rpm: u16) -> impl Future<Output = Result<()>>;
+
+    /// Moves to an absolute position in steps, only used in [`ControlMode::Position`]
+    fn move_to(&mut self, position: u32) -> impl Future<Output = Result<()>>;
+
+    /// Gets the absolute position in steps
+    fn position(&mut self) -> impl Future<Output = Result<u32>>;
+
+    /// Gets the measured speed in RPM
+    fn speed(&mut self) -> impl Future<Output = Result<f32>>;
+
+    fn status(&mut self) -> impl Future<Output = Result<MotorStatus>>;
+
+    /// Gets the active fault, if there is one
+    fn fault(&mut self) -> impl Future<Output = Result<Option<AlarmCode>>>;
+}
+
+impl<T: MotorDriver + ?Sized> MotorDriver for &mut T {
+    async fn set_enabled(&mut self, enabled: bool) -> Result<()> {
+        T::set_enabled(self, enabled).await
+    }
+
+    async fn set_velocity(&mut self, rpm: u16) -> Result<()> {
+        T::set_velocity(self, rpm).await
+    }
+
+    async fn move_to(&mut self, position: u32) -> Result<()> {
+        T::move_to(self, position).await
+    }
+
+    async fn position(&mut self) -> Result<u32> {
+        T::position(self).await
+    }
+
+    async fn speed(&mut self) -> Result<f32> {
+        T::speed(self).await
+    }
+
+    async fn status(&mut self) -> Result<MotorStatus> {
+        T::status(self).await
+    }
+
+    async fn fault(&mut self) -> Result<Option<AlarmCode>> {
+        T::fault(self).await
+    }
+}
+
+impl<I: embedded_io_async::Read + embedded_io_async::Write, const N: usize> MotorDriver
+    for Motor<I, N>
+{
+    async fn set_enabled(&mut self, enabled: bool) -> Result<()> {
+        self.set_drive_enabled(enabled).await
+    }
+
+    async fn set_velocity(&mut self, rpm: u16) -> Result<()> {
+        self.set_target_rpm(rpm).await
+    }
+
+    async fn move_to(&mut self, position: u32) -> Result<()> {
+        self.set_target_position(position).await
+    }
+
+    async fn position(&mut self) -> Result<u32> {
+        self.absolute_position().await
+    }
+
+    async fn speed(&mut self) -> Result<f32> {
+        Motor::speed(self).await
+    }
+
+    async fn status(&mut self) -> Result<MotorStatus> {
+        Motor::status(self).await
+    }
+
+    async fn fault(&mut self) -> Result<Option<AlarmCode>> {
+        self.alarm_code().await
+    }
+}
@@ -0,0 +1,186 @@
+use super::{ControlMode, MotorDriver};
+use crate::{AlarmCode, MotorStatus, Result};
+use embassy_time::Instant;
+
+// Microseconds per minute, as speeds are in RPM
+const MICROS_PER_MINUTE: u64 = 60_000_000;
+
+/// A drive that moves instantly to the commanded speed, with no load, for running motion code
+/// without hardware.
+///
+/// The position advances with [`embassy_time`], and is brought up to date whenever the drive is
+/// accessed.
+/// Like a real drive, an injected fault stops the motor until it is cleared.
+pub struct SimulatedMotor {
+    mode: ControlMode,
+    steps_per_revolution: u32,
+
+    enabled: bool,
+    rpm: u16,
+    target: u32,
+    position: u32,
+    fault: Option<AlarmCode>,
+
+    last_update: Instant,
+    // Fraction of a step moved, in step microseconds per minute
+    remainder: u64,
+}
+
+impl SimulatedMotor {
+    pub fn new(mode: ControlMode, steps_per_revolution: u32) -> Self {
+        Self {
+            mode,
+            steps_per_revolution,
+            enabled: false,
+            rpm: 0,
+            target: 0,
+            position: 0,
+            fault: None,
+            last_update: Instant::now(),
+            remainder: 0,
+        }
+    }
+
+    /// Sets the current position, and the target position, in steps
+    pub fn set_position(&mut self, position: u32) {
+        self.update();
+        self.position = position;
+        self.target = position;
+    }
+
+    /// Raises or clears a fault, the motor stops while a fault is raised
+    pub fn inject_fault(&mut self, fault: Option<AlarmCode>) {
+        self.update();
+        self.fault = fault;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn is_moving(&self) -> bool {
+        self.enabled
+            && self.fault.is_none()
+            && self.rpm > 0
+            && match self.mode {
+                ControlMode::Speed => true,
+                ControlMode::Position => self.position != self.target,
+            }
+    }
+
+    fn update(&mut self) {
+        let now = Instant::now();
+        let elapsed = now - self.last_update;
+        self.last_update = now;
+
+        if !self.is_moving() {
+            self.remainder = 0;
+            return;
+        }
+
+        // In u128, as a long time between updates would overflow u64
+        let moved =
+            self.rpm as u128 * self.steps_per_revolution as u128 * elapsed.as_micros() as u128
+                + self.remainder as u128;
+        self.remainder = (moved % MICROS_PER_MINUTE as u128) as u64;
+        let steps = moved / MICROS_PER_MINUTE as u128;
+
+        match self.mode {
+            ControlMode::Speed => {
+                // Truncating keeps the position correct as it wraps
+                self.position = self.position.wrapping_add(steps as u32);
+            }
+            ControlMode::Position => {
+                let distance = self.position.abs_diff(self.target);
+                let steps = steps.min(distance as u128) as u32;
+
+                if self.target > self.position {
+                    self.position += steps;
+                } else {
+                    self.position -= steps;
+                }
+            }
+        }
+    }
+}
+
+impl MotorDriver for SimulatedMotor {
+    async fn set_enabled(&mut self, enabled: bool) -> Result<()> {
+        self.update();
+        self.enabled = enabled;
+        Ok(())
+    }
+
+    async fn set_velocity(&mut self, rpm: u16) -> Result<()> {
+        self.update();
+        self.rpm = rpm;
+        Ok(())
+    }
+
+    async fn move_to(&mut self, position: u32) -> Result<()> {
+        self.update();
+        self.target = position;
+        Ok(())
+    }
+
+    async fn position(&mut self) -> Result<u32> {
+        self.update();
+        Ok(self.position)
+    }
+
+    async fn speed(&mut self) -> Result<f32> {
+        self.update();
+        Ok(match self.is_moving() {
+            true => self.rpm as f32,
+            false => 0.,
+        })
+    }
+
+    /// The supply voltage and temperature are fixed, and the current and PWM are always zero
+    async fn status(&mut self) -> Result<MotorStatus> {
+        Ok(MotorStatus {
+            alarm: self.fault,
+            current: 0.,
+            speed: self.speed().await?,
+            voltage: 24.,
+            temperature: 25,
+            pwm: 0,
+        })
+    }
+
+    async fn fault(&mut self) -> Result<Option<AlarmCode>> {
+        self.update();
+        Ok(self.fault)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embassy_futures::block_on;
+    use embassy_time::{Duration, Timer};
+
+    fn run(motor: &mut SimulatedMotor, rpm: u16, time: Duration) -> u32 {
+        block_on(async {
+            motor.set_velocity(rpm).await.unwrap();
+            motor.set_enabled(true).await.unwrap();
+            Timer::after(time).await;
+            motor.position().await.unwrap()
+        })
+    }
+
+    #[test]
+    fn fast_motor_does_not_overflow() {
+        let mut motor = SimulatedMotor::new(ControlMode::Speed, u32::MAX);
+        run(&mut motor, u16::MAX, Duration::from_millis(100));
+    }
+
+    #[test]
+    fn position_mode_stops_at_target() {
+        let mut motor = SimulatedMotor::new(ControlMode::Position, u32::MAX);
+        block_on(motor.move_to(1000)).unwrap();
+
+        assert_eq!(run(&mut motor, u16::MAX, Duration::from_millis(100)), 1000);
+        assert_eq!(block_on(motor.speed()), Ok(0.));
+    }
+}
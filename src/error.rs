@@ -1,8 +1,9 @@
-use defmt::Format;
+use crate::RtuBaud;
 
 pub type Result<T> = core::result::Result<T, Error>;
 
-#[derive(thiserror::Error, Debug, Format, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
 pub enum Error {
     #[error("Transport error")]
     Transport,
@@ -21,4 +22,22 @@ pub enum Error {
 
     #[error("Response contains data that was not expected")]
     UnexpectedResponseData,
+
+    #[error("Invalid argument")]
+    InvalidArgument,
+
+    #[error("Position {0} is outside of the soft limits")]
+    LimitViolation(u32),
+
+    #[error("Motor did not respond after being reconfigured")]
+    ReconfigurationFailed,
+
+    #[error("Motor {0} did not respond at any of the baud rates tried: {1:?}")]
+    BaudNotDetected(u8, [RtuBaud; 4]),
+
+    #[error("Command queue is full")]
+    QueueFull,
+
+    #[error("Request or response does not fit in the buffer")]
+    BufferTooSmall,
 }
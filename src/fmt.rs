@@ -0,0 +1,99 @@
+//! Logging macros that forward to `defmt` and/or `log`, depending on which features are enabled.
+//!
+//! Format strings must be understood by both, so arguments without a `Display` implementation
+//! are formatted with `{:?}`, and byte slices with [`Hex`].
+
+macro_rules! debug {
+    ($s:literal $(, $x:expr)* $(,)?) => {
+        {
+            #[cfg(feature = "defmt")]
+            ::defmt::debug!($s $(, $x)*);
+            #[cfg(feature = "log")]
+            ::log::debug!($s $(, $x)*);
+            #[cfg(not(any(feature = "defmt", feature = "log")))]
+            let _ = ($( & $x ),*);
+        }
+    };
+}
+
+macro_rules! info {
+    ($s:literal $(, $x:expr)* $(,)?) => {
+        {
+            #[cfg(feature = "defmt")]
+            ::defmt::info!($s $(, $x)*);
+            #[cfg(feature = "log")]
+            ::log::info!($s $(, $x)*);
+            #[cfg(not(any(feature = "defmt", feature = "log")))]
+            let _ = ($( & $x ),*);
+        }
+    };
+}
+
+macro_rules! warn {
+    ($s:literal $(, $x:expr)* $(,)?) => {
+        {
+            #[cfg(feature = "defmt")]
+            ::defmt::warn!($s $(, $x)*);
+            #[cfg(feature = "log")]
+            ::log::warn!($s $(, $x)*);
+            #[cfg(not(any(feature = "defmt", feature = "log")))]
+            let _ = ($( & $x ),*);
+        }
+    };
+}
+
+macro_rules! error {
+    ($s:literal $(, $x:expr)* $(,)?) => {
+        {
+            #[cfg(feature = "defmt")]
+            ::defmt::error!($s $(, $x)*);
+            #[cfg(feature = "log")]
+            ::log::error!($s $(, $x)*);
+            #[cfg(not(any(feature = "defmt", feature = "log")))]
+            let _ = ($( & $x ),*);
+        }
+    };
+}
+
+/// Formats bytes as hex, e.g. `01 03 00 0e`.
+pub(crate) struct Hex<'a>(pub &'a [u8]);
+
+impl core::fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for (i, b) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{b:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Hex<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "{=[u8]:02x}", self.0)
+    }
+}
+
+// defmt output is discarded by host tests, which have no probe to send it to
+#[cfg(all(test, feature = "defmt"))]
+mod test_logger {
+    #[defmt::global_logger]
+    struct Logger;
+
+    unsafe impl defmt::Logger for Logger {
+        fn acquire() {}
+        unsafe fn flush() {}
+        unsafe fn release() {}
+        unsafe fn write(_bytes: &[u8]) {}
+    }
+
+    defmt::timestamp!("");
+
+    #[defmt::panic_handler]
+    fn panic() -> ! {
+        panic!("defmt panic")
+    }
+}
@@ -1,9 +1,44 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
+#[macro_use]
+mod fmt;
+
+mod capture;
+mod driver;
 mod error;
+mod monitor;
 mod motor;
+mod queue;
+mod shared;
+mod supervisor;
+mod trajectory;
+mod transport;
 mod types;
+mod watchdog;
 
+pub use capture::{
+    CaptureBuffer, CaptureReader, CaptureSink, DecodedFrame, Record, RecordKind, Recorder, Replay,
+    ReplayError,
+};
+pub use driver::{ControlMode, DriverCall, MockMotor, MotorDriver, SimulatedMotor};
 pub use error::{Error, Result};
-pub use motor::Motor;
-pub use types::{AlarmCode, Direction, RtuBaud};
+pub use monitor::{Monitor, MonitorConfig, MonitorEvent, Threshold};
+#[cfg(feature = "alarm-history")]
+pub use motor::{AlarmHistory, AlarmRecord};
+pub use motor::{
+    AxisMove, CoordinatedMove, DiscoveredMotor, EchoCheck, LimitAction, LimitEvent, Motor,
+    Protocol, ReceiveMode, ScanProgress, ScanSummary, SetpointCommand, SoftLimits, TrackingReport,
+    Trigger, WriteBatch, WriteReport, scan,
+};
+#[cfg(feature = "timing")]
+pub use motor::{FunctionTiming, HISTOGRAM_BOUNDS, PhaseStats, TimingStats, TransactionTiming};
+pub use queue::{Command, CommandQueue, LatencyStats, Priority, QueueStats, Reply, Ticket};
+pub use shared::{SharedMotor, SharedMotorGuard};
+pub use supervisor::{Fault, FaultKind, Supervisor, SupervisorConfig};
+pub use trajectory::{Setpoints, Trajectory, TrajectoryLimits};
+pub use transport::{BaudRateAdapter, SetBaudRate};
+pub use types::{
+    AlarmCode, AlarmSeverity, DataBits, Direction, MotorStatus, Parity, RtuBaud, SerialConfig,
+    StopBits, StopMode,
+};
+pub use watchdog::{Watchdog, WatchdogGuard, WatchdogTrip};
@@ -0,0 +1,205 @@
+use crate::{AlarmCode, MotorDriver, MotorStatus};
+use embassy_sync::{pubsub::DynImmediatePublisher, watch::DynSender};
+use embassy_time::{Duration, Ticker};
+
+/// Polling rate and thresholds used by a [`Monitor`], `None` disables a threshold.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone)]
+pub struct MonitorConfig {
+    /// Interval between reads of the status registers
+    pub interval: Duration,
+
+    /// Number of consecutive failed reads after which communication is considered lost
+    pub comm_loss_count: u8,
+
+    /// Maximum temperature in degrees C
+    pub max_temperature: Option<u16>,
+
+    /// Minimum supply voltage in V
+    pub min_voltage: Option<f32>,
+
+    /// Maximum supply voltage in V
+    pub max_voltage: Option<f32>,
+
+    /// Maximum current in A
+    pub max_current: Option<f32>,
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Threshold {
+    Overtemperature,
+    Undervoltage,
+    Overvoltage,
+    Overcurrent,
+}
+
+impl Threshold {
+    const ALL: [Self; 4] = [
+        Self::Overtemperature,
+        Self::Undervoltage,
+        Self::Overvoltage,
+        Self::Overcurrent,
+    ];
+
+    fn mask(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// A change in the health of a motor, published by a [`Monitor`].
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum MonitorEvent {
+    /// The motor reported an alarm (or a different alarm to the previous one)
+    AlarmRaised(AlarmCode),
+
+    /// The motor no longer reports an alarm
+    AlarmCleared,
+
+    /// A threshold was exceeded, with the measured value
+    ThresholdExceeded(Threshold, f32),
+
+    /// A previously exceeded threshold is no longer exceeded, with the measured value
+    ThresholdCleared(Threshold, f32),
+
+    /// The status registers could not be read
+    CommLost,
+
+    /// The status registers could be read again after communication was lost
+    CommRestored,
+}
+
+/// Polls the status of a motor, publishing the latest status and changes in its health.
+///
+/// The latest status is sent to an [`embassy_sync::watch::Watch`] and events to an
+/// [`embassy_sync::pubsub::PubSubChannel`], allowing other tasks to react to them without
+/// accessing the bus themselves.
+pub struct Monitor<'a> {
+    config: MonitorConfig,
+
+    status: DynSender<'a, MotorStatus>,
+    events: DynImmediatePublisher<'a, MonitorEvent>,
+
+    alarm: Option<AlarmCode>,
+    exceeded: u8,
+    failed_reads: u8,
+    comm_lost: bool,
+}
+
+impl<'a> Monitor<'a> {
+    pub fn new(
+        config: MonitorConfig,
+        status: DynSender<'a, MotorStatus>,
+        events: DynImmediatePublisher<'a, MonitorEvent>,
+    ) -> Self {
+        Self {
+            config,
+            status,
+            events,
+            alarm: None,
+            exceeded: 0,
+            failed_reads: 0,
+            comm_lost: false,
+        }
+    }
+
+    /// Polls the motor at the configured interval, forever
+    ///
+    /// Polling a [`SharedMotor`](crate::SharedMotor) through `&shared` also updates its cached
+    /// status.
+    pub async fn run(&mut self, mut motor: impl MotorDriver) -> ! {
+        let mut ticker = Ticker::every(self.config.interval);
+
+        loop {
+            self.poll(&mut motor).await;
+            ticker.next().await;
+        }
+    }
+
+    /// Reads the status of the motor once, publishing the status and any events
+    pub async fn poll(&mut self, mut motor: impl MotorDriver) {
+        match motor.status().await {
+            Ok(status) => {
+                self.failed_reads = 0;
+                if self.comm_lost {
+                    info!("Motor communication restored");
+                    self.comm_lost = false;
+                    self.events.publish_immediate(MonitorEvent::CommRestored);
+                }
+
+                self.update(&status);
+                self.status.send(status);
+            }
+            Err(e) => {
+                warn!("Failed to read motor status: {}", e);
+
+                self.failed_reads = self.failed_reads.saturating_add(1);
+                if !self.comm_lost && self.failed_reads >= self.config.comm_loss_count {
+                    self.comm_lost = true;
+                    self.events.publish_immediate(MonitorEvent::CommLost);
+                }
+            }
+        }
+    }
+
+    fn update(&mut self, status: &MotorStatus) {
+        if status.alarm != self.alarm {
+            match status.alarm {
+                Some(alarm) => {
+                    warn!("Motor alarm: {:?}", alarm);
+                    self.events
+                        .publish_immediate(MonitorEvent::AlarmRaised(alarm));
+                }
+                None => {
+                    info!("Motor alarm cleared");
+                    self.events.publish_immediate(MonitorEvent::AlarmCleared);
+                }
+            }
+            self.alarm = status.alarm;
+        }
+
+        for threshold in Threshold::ALL {
+            let (value, exceeded) = match threshold {
+                Threshold::Overtemperature => (
+                    status.temperature as f32,
+                    self.config
+                        .max_temperature
+                        .is_some_and(|max| status.temperature > max),
+                ),
+                Threshold::Undervoltage => (
+                    status.voltage,
+                    self.config
+                        .min_voltage
+                        .is_some_and(|min| status.voltage < min),
+                ),
+                Threshold::Overvoltage => (
+                    status.voltage,
+                    self.config
+                        .max_voltage
+                        .is_some_and(|max| status.voltage > max),
+                ),
+                Threshold::Overcurrent => (
+                    status.current,
+                    self.config
+                        .max_current
+                        .is_some_and(|max| status.current > max),
+                ),
+            };
+
+            let was_exceeded = self.exceeded & threshold.mask() != 0;
+
+            if exceeded && !was_exceeded {
+                warn!("Motor threshold exceeded: {:?} ({})", threshold, value);
+                self.exceeded |= threshold.mask();
+                self.events
+                    .publish_immediate(MonitorEvent::ThresholdExceeded(threshold, value));
+            } else if !exceeded && was_exceeded {
+                info!("Motor threshold cleared: {:?} ({})", threshold, value);
+                self.exceeded &= !threshold.mask();
+                self.events
+                    .publish_immediate(MonitorEvent::ThresholdCleared(threshold, value));
+            }
+        }
+    }
+}
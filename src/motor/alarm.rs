@@ -0,0 +1,31 @@
+use super::Motor;
+use crate::{AlarmCode, Result};
+use embassy_time::{Duration, Timer};
+
+impl<I: embedded_io_async::Read + embedded_io_async::Write, const N: usize> Motor<I, N> {
+    /// Attempts to clear the active alarm by disabling the drive
+    ///
+    /// The drive is disabled, then after the settle time the alarm is checked again.
+    /// The drive is left disabled whether or not the alarm cleared, it is up to the caller to
+    /// enable it again once it is safe for the motor to move.
+    /// Returns the alarm that is still active, or `None` if the alarm was cleared (or there was
+    /// no alarm to begin with).
+    pub async fn clear_alarm(&mut self, settle: Duration) -> Result<Option<AlarmCode>> {
+        let Some(alarm) = self.alarm_code().await? else {
+            return Ok(None);
+        };
+        info!("Attempting to clear alarm {:?}", alarm);
+
+        self.set_drive_enabled(false).await?;
+        Timer::after(settle).await;
+
+        let alarm = self.alarm_code().await?;
+
+        match alarm {
+            Some(alarm) => warn!("Alarm {:?} did not clear", alarm),
+            None => info!("Alarm cleared, drive left disabled"),
+        }
+
+        Ok(alarm)
+    }
+}
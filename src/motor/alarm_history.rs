@@ -0,0 +1,93 @@
+use super::Motor;
+use crate::AlarmCode;
+use embassy_time::Instant;
+
+const HISTORY_CAPACITY: usize = 8;
+
+/// An alarm reported by the motor.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlarmRecord {
+    pub code: AlarmCode,
+
+    /// When the alarm was first seen
+    pub raised: Instant,
+
+    /// When the alarm was first seen to no longer be active, `None` if it is still active
+    pub cleared: Option<Instant>,
+}
+
+/// The most recent alarms reported by the motor.
+///
+/// Alarms are recorded whenever the alarm code is read, see [`Motor::alarm_code`] and
+/// [`Motor::status`].
+/// Once full, the oldest alarm is forgotten when a new one is recorded.
+#[derive(Debug, Default)]
+pub struct AlarmHistory {
+    records: [Option<AlarmRecord>; HISTORY_CAPACITY],
+    // Index the next record will be written to
+    next: usize,
+    active: Option<AlarmCode>,
+}
+
+impl AlarmHistory {
+    /// Gets the recorded alarms, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &AlarmRecord> {
+        let (newest, oldest) = self.records.split_at(self.next);
+        oldest.iter().chain(newest).flatten()
+    }
+
+    pub fn latest(&self) -> Option<&AlarmRecord> {
+        self.records[(self.next + HISTORY_CAPACITY - 1) % HISTORY_CAPACITY].as_ref()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub(super) fn observe(&mut self, alarm: Option<AlarmCode>) {
+        if alarm == self.active {
+            return;
+        }
+
+        let now = Instant::now();
+
+        if self.active.is_some()
+            && let Some(latest) =
+                &mut self.records[(self.next + HISTORY_CAPACITY - 1) % HISTORY_CAPACITY]
+        {
+            latest.cleared = Some(now);
+        }
+
+        if let Some(code) = alarm {
+            warn!("Alarm raised: {:?} ({})", code, code.description());
+
+            self.records[self.next] = Some(AlarmRecord {
+                code,
+                raised: now,
+                cleared: None,
+            });
+            self.next = (self.next + 1) % HISTORY_CAPACITY;
+        }
+
+        self.active = alarm;
+    }
+}
+
+impl<I: embedded_io_async::Read + embedded_io_async::Write, const N: usize> Motor<I, N> {
+    pub fn alarm_history(&self) -> &AlarmHistory {
+        &self.alarms
+    }
+
+    pub fn clear_alarm_history(&mut self) {
+        self.alarms.clear();
+    }
+}
@@ -0,0 +1,421 @@
+use super::{
+    Motor, Protocol,
+    parameters::{dir_polarity_to_raw, position_to_raw, speed_feed_to_raw, speed_i_time_to_raw},
+};
+use crate::{Direction, Error, Result};
+use embassy_time::Duration;
+
+// Number of register writes a batch can hold
+const CAPACITY: usize = 32;
+
+// The two halves of the target position, which must always be written in the same request, or
+// the motor moves to a position made of one new and one stale half
+const TARGET_POSITION: u16 = 0x0C;
+
+fn is_target_position(a: &(u16, u16), b: &(u16, u16)) -> bool {
+    a.0 == TARGET_POSITION && b.0 == TARGET_POSITION + 1
+}
+
+/// Parameter writes to be sent to a motor together, see [`Motor::write_batch`].
+///
+/// Writes to contiguous registers are merged into a single `WriteMultipleRegisters` request.
+/// If a register is written more than once, the last value is used.
+#[derive(Debug, Clone)]
+pub struct WriteBatch {
+    writes: [(u16, u16); CAPACITY],
+    len: usize,
+    overflowed: bool,
+    target_position: Option<u32>,
+}
+
+impl Default for WriteBatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WriteBatch {
+    pub fn new() -> Self {
+        Self {
+            writes: [(0, 0); CAPACITY],
+            len: 0,
+            overflowed: false,
+            target_position: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0 && self.target_position.is_none()
+    }
+
+    pub fn drive_enabled(&mut self, value: bool) -> &mut Self {
+        self.push(0x01, value as u16)
+    }
+
+    pub fn target_rpm(&mut self, value: u16) -> &mut Self {
+        self.push(0x02, value)
+    }
+
+    pub fn acceleration(&mut self, value: u16) -> &mut Self {
+        self.push(0x03, value)
+    }
+
+    pub fn weak_magnetic_angle(&mut self, value: u16) -> &mut Self {
+        self.push(0x04, value)
+    }
+
+    pub fn speed_kp(&mut self, value: u16) -> &mut Self {
+        self.push(0x05, value)
+    }
+
+    pub fn speed_i_time(&mut self, value: Duration) -> &mut Self {
+        self.push(0x06, speed_i_time_to_raw(value))
+    }
+
+    pub fn position_kp(&mut self, value: u16) -> &mut Self {
+        self.push(0x07, value)
+    }
+
+    pub fn speed_feed(&mut self, value: f32) -> &mut Self {
+        self.push(0x08, speed_feed_to_raw(value))
+    }
+
+    pub fn dir_polarity(&mut self, value: Direction) -> &mut Self {
+        self.push(0x09, dir_polarity_to_raw(value))
+    }
+
+    pub fn electronic_gear_numerator(&mut self, value: u16) -> &mut Self {
+        self.push(0x0A, value)
+    }
+
+    pub fn electronic_gear_denominator(&mut self, value: u16) -> &mut Self {
+        self.push(0x0B, value)
+    }
+
+    /// Sets the target position, which is checked against the soft limits when the batch is
+    /// written
+    pub fn target_position(&mut self, value: u32) -> &mut Self {
+        self.target_position = Some(value);
+        self
+    }
+
+    pub fn still_maximum_allowed_current(&mut self, value: u16) -> &mut Self {
+        self.push(0x18, value)
+    }
+
+    pub fn specific_function(&mut self, value: u16) -> &mut Self {
+        self.push(0x19, value)
+    }
+
+    fn push(&mut self, register: u16, value: u16) -> &mut Self {
+        if let Some(write) = self.writes[..self.len]
+            .iter_mut()
+            .find(|(r, _)| *r == register)
+        {
+            write.1 = value;
+        } else if self.len < CAPACITY {
+            self.writes[self.len] = (register, value);
+            self.len += 1;
+        } else {
+            self.overflowed = true;
+        }
+
+        self
+    }
+}
+
+/// The result of writing each register of a [`WriteBatch`].
+///
+/// Kept small enough to return on the stack of a microcontroller: only the first error is kept,
+/// with the registers that were and were not written.
+#[derive(Debug, Clone, Default)]
+pub struct WriteReport {
+    // Bit masks of registers, which are all below 0x20
+    written: u32,
+    failed: u32,
+
+    /// The first error, if any register could not be written
+    pub error: Option<Error>,
+
+    /// Number of requests sent
+    pub requests: usize,
+}
+
+impl WriteReport {
+    /// Gets the registers that were written, in register order
+    pub fn written(&self) -> impl Iterator<Item = u16> {
+        registers(self.written)
+    }
+
+    /// Gets the registers that could not be written, in register order
+    pub fn failed(&self) -> impl Iterator<Item = u16> {
+        registers(self.failed)
+    }
+
+    /// If every register was written
+    pub fn is_ok(&self) -> bool {
+        self.failed == 0
+    }
+
+    fn record(&mut self, register: u16, result: &Result<()>) {
+        match result {
+            Ok(()) => self.written |= 1 << register,
+            Err(e) => {
+                self.failed |= 1 << register;
+                self.error.get_or_insert_with(|| e.clone());
+            }
+        }
+    }
+}
+
+fn registers(mask: u32) -> impl Iterator<Item = u16> {
+    (0..32).filter(move |r| mask & (1 << r) != 0)
+}
+
+impl<I: embedded_io_async::Read + embedded_io_async::Write, const N: usize> Motor<I, N> {
+    /// Writes all of the parameters in a batch, using as few requests as possible
+    ///
+    /// If the motor rejects a multiple register write, the registers in it are written one at a
+    /// time instead, apart from the two halves of the target position, which are always written
+    /// together.
+    /// Fails without writing anything if the batch overflowed, or the target position is outside
+    /// of the soft limits.
+    pub async fn write_batch(&mut self, batch: &WriteBatch) -> Result<WriteReport> {
+        if batch.overflowed {
+            return Err(Error::InvalidArgument);
+        }
+
+        let mut writes = [(0u16, 0u16); CAPACITY + 2];
+        writes[..batch.len].copy_from_slice(&batch.writes[..batch.len]);
+        let mut len = batch.len;
+
+        if let Some(position) = batch.target_position {
+            let [lsb, msb] = position_to_raw(self.limit_position(position)?);
+            writes[len] = (0x0C, lsb);
+            writes[len + 1] = (0x0D, msb);
+            len += 2;
+        }
+
+        let writes = &mut writes[..len];
+        writes.sort_unstable_by_key(|(r, _)| *r);
+
+        let mut report = WriteReport::default();
+
+        let max_len = self.max_write_len();
+        let mut start = 0;
+
+        while start < writes.len() {
+            // Find the end of the contiguous run, limited to what fits in one request
+            let mut end = start + 1;
+            while end < writes.len()
+                && end - start < max_len
+                && writes[end].0 == writes[end - 1].0 + 1
+            {
+                end += 1;
+            }
+
+            // Never split the target position between requests
+            if end < writes.len() && is_target_position(&writes[end - 1], &writes[end]) {
+                if end - start == 1 {
+                    // Only when the buffer cannot hold two registers
+                    for (register, _) in &writes[start..start + 2] {
+                        report.record(*register, &Err(Error::BufferTooSmall));
+                    }
+                    start += 2;
+                    continue;
+                }
+                end -= 1;
+            }
+
+            let run = &writes[start..end];
+            let mut values = [0u16; CAPACITY + 2];
+            for (value, (_, v)) in values.iter_mut().zip(run) {
+                *value = *v;
+            }
+
+            report.requests += 1;
+            let result = if run.len() == 1 {
+                self.write_one_word_parameter(run[0].0, run[0].1, Ok).await
+            } else {
+                match self.write_parameters(run[0].0, &values[..run.len()]).await {
+                    // Retried one at a time, unless the run is only the target position, which cannot be split
+                    Err(Error::Modbus) if !matches!(run, [a, b] if is_target_position(a, b)) => {
+                        warn!(
+                            "Multiple register write to {:x} rejected, writing one at a time",
+                            run[0].0
+                        );
+                        for unit in run.chunk_by(is_target_position) {
+                            report.requests += 1;
+                            let result = match unit {
+                                [(register, value)] => {
+                                    self.write_one_word_parameter(*register, *value, Ok).await
+                                }
+                                _ => {
+                                    self.write_parameters(unit[0].0, &[unit[0].1, unit[1].1])
+                                        .await
+                                }
+                            };
+                            for (register, _) in unit {
+                                report.record(*register, &result);
+                            }
+                        }
+                        start = end;
+                        continue;
+                    }
+                    result => result,
+                }
+            };
+
+            debug!(
+                "Wrote {} registers from {:x}: {:?}",
+                run.len(),
+                run[0].0,
+                result
+            );
+            for (register, _) in run {
+                report.record(*register, &result);
+            }
+
+            start = end;
+        }
+
+        Ok(report)
+    }
+
+    // Maximum number of registers that fit in a write request in the buffer
+    fn max_write_len(&self) -> usize {
+        let overhead = match self.protocol {
+            // MBAP header, function code, address, count and byte count
+            Protocol::Tcp => 7 + 6,
+            // Address, function code, address, count, byte count and CRC
+            Protocol::Rtu | Protocol::RtuOverTcp => 1 + 6 + 2,
+        };
+
+        (self.buffer.len().saturating_sub(overhead) / 2).clamp(1, 123)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::testing::{TestBus, motor},
+        *,
+    };
+    use embassy_futures::block_on;
+    use std::vec::Vec;
+
+    // The function, first register and count of each request sent
+    fn requests(bus: &TestBus) -> Vec<(u8, u16, u16)> {
+        bus.requests
+            .iter()
+            .map(|r| {
+                let word = |i: usize| u16::from_be_bytes([r[i], r[i + 1]]);
+                match r[1] {
+                    0x06 => (0x06, word(2), 1),
+                    function => (function, word(2), word(4)),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn contiguous_writes_are_merged_across_gaps() {
+        let mut motor = motor(TestBus::new());
+        let mut batch = WriteBatch::new();
+        batch
+            .speed_kp(5)
+            .target_rpm(100)
+            .acceleration(20)
+            .electronic_gear_numerator(10)
+            .target_rpm(200)
+            .specific_function(1)
+            .still_maximum_allowed_current(50);
+
+        let report = block_on(motor.write_batch(&batch)).unwrap();
+
+        assert!(report.is_ok());
+        assert_eq!(report.requests, 4);
+        assert!(report.written().eq([0x02, 0x03, 0x05, 0x0A, 0x18, 0x19]));
+        assert_eq!(
+            requests(motor.transport()),
+            [
+                (0x10, 0x02, 2),
+                (0x06, 0x05, 1),
+                (0x06, 0x0A, 1),
+                (0x10, 0x18, 2)
+            ]
+        );
+        assert_eq!(motor.transport().registers[0x02], 200);
+        assert_eq!(motor.transport().registers[0x19], 1);
+    }
+
+    #[test]
+    fn rejected_multiple_write_falls_back_to_single_writes() {
+        let mut bus = TestBus::new();
+        bus.reject_multiple = true;
+        let mut motor = motor(bus);
+        let mut batch = WriteBatch::new();
+        batch
+            .target_rpm(100)
+            .acceleration(20)
+            .target_position(0x0001_0002);
+
+        let report = block_on(motor.write_batch(&batch)).unwrap();
+
+        // The target position cannot be written one half at a time
+        assert!(report.written().eq([0x02, 0x03]));
+        assert!(report.failed().eq([0x0C, 0x0D]));
+        assert_eq!(report.error, Some(Error::Modbus));
+        assert_eq!(
+            requests(motor.transport()),
+            [
+                (0x10, 0x02, 2),
+                (0x06, 0x02, 1),
+                (0x06, 0x03, 1),
+                (0x10, 0x0C, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn target_position_is_never_split() {
+        let mut motor = motor(TestBus::new()).with_buffer_size::<{ 9 + 2 * 11 }>();
+        let mut batch = WriteBatch::new();
+        batch
+            .target_rpm(1)
+            .acceleration(2)
+            .weak_magnetic_angle(3)
+            .speed_kp(4)
+            .speed_i_time(Duration::from_millis(10))
+            .position_kp(6)
+            .speed_feed(0.)
+            .dir_polarity(Direction::Clockwise)
+            .electronic_gear_numerator(9)
+            .electronic_gear_denominator(10)
+            .target_position(0x0001_0002);
+
+        let report = block_on(motor.write_batch(&batch)).unwrap();
+
+        assert!(report.is_ok());
+        assert_eq!(
+            requests(motor.transport()),
+            [(0x10, 0x02, 10), (0x10, 0x0C, 2)]
+        );
+        assert_eq!(motor.transport().registers[0x0C..0x0E], [0x0002, 0x0001]);
+    }
+
+    #[test]
+    fn overflowed_batch_writes_nothing() {
+        let mut motor = motor(TestBus::new());
+        let mut batch = WriteBatch::new();
+        for i in 0..=CAPACITY as u16 {
+            batch.push(0x20 + i, 0);
+        }
+
+        assert_eq!(
+            block_on(motor.write_batch(&batch)).err(),
+            Some(Error::InvalidArgument)
+        );
+        assert!(motor.transport().requests.is_empty());
+    }
+}
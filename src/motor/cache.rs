@@ -0,0 +1,249 @@
+use super::Motor;
+use core::ops::Range;
+
+// One past the highest register that is cached
+const REGISTERS: usize = 0x1A;
+
+// Registers that hold configuration, whose value only changes when written.
+// Not cached:
+// - 0x00, 0x01 and 0x14, which are commands that must always reach the motor
+// - 0x0C-0x0D and 0x16-0x17, the target and absolute positions
+// - 0x0E-0x13, the live status
+// - 0x15, the device address, which is read to check that the motor responds
+fn is_cacheable(register: u16) -> bool {
+    matches!(register, 0x02..=0x0B | 0x18..=0x19)
+}
+
+// Registers that are always written, even when they already hold the value.
+// A target speed of 0 stops the motor, which must not be skipped because the cache missed a change,
+// e.g. a saved speed being loaded when the motor was power cycled.
+fn is_always_written(register: u16) -> bool {
+    register == 0x02
+}
+
+fn registers(register: u16, count: usize) -> Option<Range<u16>> {
+    let end = register.checked_add(u16::try_from(count).ok()?)?;
+    Some(register..end)
+}
+
+/// Last known value of each configuration register of a motor.
+#[derive(Default)]
+pub(super) struct RegisterCache {
+    enabled: bool,
+
+    // Address of the motor the values belong to
+    address: u8,
+
+    values: [Option<u16>; REGISTERS],
+}
+
+impl RegisterCache {
+    /// Gets the values of registers, if they are all cached
+    pub(super) fn get(&mut self, address: u8, register: u16, values: &mut [u16]) -> bool {
+        if !self.check_address(address) {
+            return false;
+        }
+
+        let Some(registers) = registers(register, values.len()) else {
+            return false;
+        };
+        if !registers.clone().all(is_cacheable) {
+            return false;
+        }
+
+        for (r, value) in registers.zip(values.iter_mut()) {
+            match self.values[r as usize] {
+                Some(v) => *value = v,
+                None => return false,
+            }
+        }
+
+        true
+    }
+
+    /// If registers already hold values, so that writing them would not change anything
+    pub(super) fn holds(&mut self, address: u8, register: u16, values: &[u16]) -> bool {
+        if registers(register, values.len()).is_some_and(|mut r| r.any(is_always_written)) {
+            return false;
+        }
+
+        let mut cached = [0u16; REGISTERS];
+        match cached.get_mut(..values.len()) {
+            Some(cached) => self.get(address, register, cached) && cached == values,
+            None => false,
+        }
+    }
+
+    /// Records values read from, or written to, registers
+    pub(super) fn store(&mut self, address: u8, register: u16, values: &[u16]) {
+        if !self.check_address(address) {
+            return;
+        }
+        let Some(registers) = registers(register, values.len()) else {
+            return;
+        };
+
+        for (r, value) in registers.zip(values) {
+            if is_cacheable(r) {
+                self.values[r as usize] = Some(*value);
+            }
+        }
+    }
+
+    /// Forgets the values of registers that may have changed without the motor confirming it
+    pub(super) fn forget(&mut self, register: u16, count: usize) {
+        let Some(registers) = registers(register, count) else {
+            self.invalidate();
+            return;
+        };
+
+        for r in registers {
+            if let Some(value) = self.values.get_mut(r as usize) {
+                *value = None;
+            }
+        }
+    }
+
+    pub(super) fn invalidate(&mut self) {
+        self.values = [None; REGISTERS];
+    }
+
+    // Values are only kept for one motor, switching to another address forgets them
+    fn check_address(&mut self, address: u8) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        if address != self.address {
+            self.invalidate();
+            self.address = address;
+        }
+
+        true
+    }
+}
+
+impl<I: embedded_io_async::Read + embedded_io_async::Write, const N: usize> Motor<I, N> {
+    /// Enables or disables caching the configuration registers, disabled by default
+    ///
+    /// While enabled, reads of configuration registers whose value is known are served from the
+    /// cache, and writes that would not change the value are skipped.
+    /// The live status (0x0E-0x13), positions and commands are never cached, and the target speed
+    /// and drive enable are always written, so that stopping the motor is never skipped.
+    /// The cache is cleared after any failed request, a change of baud rate or address, and a
+    /// custom command with an unknown effect.
+    /// Changes made by another master, or by the motor's own front panel, are not seen, see
+    /// [`Motor::invalidate_register_cache`].
+    pub fn set_register_cache(&mut self, enabled: bool) {
+        self.cache.enabled = enabled;
+        self.cache.invalidate();
+    }
+
+    /// If the configuration registers are cached, see [`Motor::set_register_cache`]
+    pub fn register_cache_enabled(&self) -> bool {
+        self.cache.enabled
+    }
+
+    /// Forgets all cached register values, e.g. after the motor was power cycled
+    pub fn invalidate_register_cache(&mut self) {
+        self.cache.invalidate();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::testing::{TestBus, motor},
+        *,
+    };
+    use crate::Error;
+    use embassy_futures::block_on;
+
+    fn cache() -> RegisterCache {
+        RegisterCache {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn stored_values_are_returned() {
+        let mut cache = cache();
+        cache.store(1, 0x03, &[20, 30]);
+
+        let mut values = [0; 2];
+        assert!(cache.get(1, 0x03, &mut values));
+        assert_eq!(values, [20, 30]);
+
+        // Partly known, or not cacheable
+        assert!(!cache.get(1, 0x03, &mut [0; 3]));
+        assert!(!cache.get(1, 0x0E, &mut [0; 1]));
+    }
+
+    #[test]
+    fn live_and_command_registers_are_not_stored() {
+        let mut cache = cache();
+        cache.store(1, 0x0B, &[1, 2, 3]);
+
+        assert!(cache.get(1, 0x0B, &mut [0; 1]));
+        assert!(!cache.get(1, 0x0C, &mut [0; 1]));
+        assert!(!cache.get(1, 0x0D, &mut [0; 1]));
+    }
+
+    #[test]
+    fn target_speed_is_always_written() {
+        let mut cache = cache();
+        cache.store(1, 0x02, &[100, 20]);
+
+        assert!(cache.holds(1, 0x03, &[20]));
+        assert!(!cache.holds(1, 0x03, &[21]));
+        assert!(!cache.holds(1, 0x02, &[100]));
+        assert!(!cache.holds(1, 0x02, &[100, 20]));
+    }
+
+    #[test]
+    fn switching_address_forgets_values() {
+        let mut cache = cache();
+        cache.store(1, 0x03, &[20]);
+
+        assert!(!cache.get(2, 0x03, &mut [0; 1]));
+        assert!(!cache.get(1, 0x03, &mut [0; 1]));
+    }
+
+    #[test]
+    fn disabled_cache_holds_nothing() {
+        let mut cache = RegisterCache::default();
+        cache.store(1, 0x03, &[20]);
+
+        assert!(!cache.get(1, 0x03, &mut [0; 1]));
+    }
+
+    #[test]
+    fn reads_are_served_from_cache() {
+        let mut motor = motor(TestBus::new());
+        motor.set_register_cache(true);
+        motor.transport_mut().registers[0x03] = 20;
+
+        assert_eq!(block_on(motor.acceleration()), Ok(20));
+        assert_eq!(block_on(motor.acceleration()), Ok(20));
+        block_on(motor.set_acceleration(20)).unwrap();
+
+        assert_eq!(motor.transport().requests.len(), 1);
+    }
+
+    #[test]
+    fn failed_request_invalidates_cache() {
+        let mut motor = motor(TestBus::new());
+        motor.set_register_cache(true);
+        block_on(motor.set_acceleration(20)).unwrap();
+        block_on(motor.set_speed_kp(5)).unwrap();
+
+        motor.transport_mut().silent = 1;
+        assert_eq!(block_on(motor.set_speed_kp(6)), Err(Error::Timeout));
+
+        // The motor may have applied the write, or been power cycled
+        motor.transport_mut().registers[0x03] = 30;
+        assert_eq!(block_on(motor.acceleration()), Ok(30));
+        assert_eq!(motor.transport().requests.len(), 4);
+    }
+}
@@ -0,0 +1,185 @@
+use super::{
+    Motor, SoftLimits,
+    parameters::{position_from_raw, position_to_raw},
+};
+use crate::{Error, Result};
+use embassy_time::{Duration, Instant, Timer};
+
+/// The move of a single axis in a [`CoordinatedMove`].
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone)]
+pub struct AxisMove {
+    /// Modbus address of the motor driving the axis
+    pub address: u8,
+
+    /// Absolute position to move to
+    pub target: u32,
+
+    /// Soft limits of the axis, which the target is checked against
+    pub limits: Option<SoftLimits>,
+}
+
+/// How the staged moves of a [`CoordinatedMove`] are started.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Trigger {
+    /// Drives are disabled while their targets are written, then all are enabled at once by a
+    /// single broadcast frame
+    ///
+    /// This starts the axes closest together, but only use it if these hazards are acceptable:
+    /// - **every drive on the bus is enabled**, including those that are not part of the move,
+    ///   which then start moving to whatever target they hold
+    /// - the drives of the axes have no holding torque while the targets are written, so axes
+    ///   under load (e.g. vertical axes) may move
+    BroadcastEnableAll,
+
+    /// Targets are written back to back with the drives enabled, each axis starts as soon as its
+    /// target is received
+    #[default]
+    Sequential,
+}
+
+/// Parameters of a move of several axes on the same bus that start and finish together.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone)]
+pub struct CoordinatedMove {
+    /// Maximum speed in RPM of the axis with the longest move
+    ///
+    /// The speed of every other axis is scaled down in proportion to its distance.
+    pub target_rpm: u16,
+
+    /// Acceleration of the axis with the longest move
+    ///
+    /// The acceleration of every other axis is scaled down in proportion to its distance.
+    pub acceleration: u16,
+
+    /// How the axes are started, see [`Trigger::BroadcastEnableAll`] for the hazards of starting
+    /// them together
+    pub trigger: Trigger,
+
+    /// Distance in steps from the target at which an axis is considered to have arrived
+    pub tolerance: u32,
+
+    /// Interval between checking the position of the axes while waiting for them to arrive
+    pub poll_interval: Duration,
+
+    /// Maximum time to wait for all axes to arrive
+    pub timeout: Duration,
+}
+
+impl<I: embedded_io_async::Read + embedded_io_async::Write, const N: usize> Motor<I, N> {
+    /// Moves several motors on the same bus so that they start and arrive at the same time
+    ///
+    /// The motors must already be in position mode.
+    /// Each axis is addressed individually, without changing the address or soft limits of this
+    /// [`Motor`], so it is left unchanged if the move is cancelled.
+    /// Each axis is checked against its own soft limits, rather than those of this [`Motor`].
+    /// Requests to the axes bypass the register cache.
+    /// Returns once all axes are within the tolerance of their targets.
+    pub async fn coordinated_move<const A: usize>(
+        &mut self,
+        axes: &[AxisMove; A],
+        params: &CoordinatedMove,
+    ) -> Result<()> {
+        if axes
+            .iter()
+            .any(|a| a.limits.as_ref().is_some_and(|l| l.min > l.max))
+        {
+            return Err(Error::InvalidArgument);
+        }
+
+        let mut targets = [0u32; A];
+        let mut distances = [0u32; A];
+
+        for ((axis, target), distance) in axes.iter().zip(&mut targets).zip(&mut distances) {
+            *target = self.check_position(axis.limits.as_ref(), axis.target)?;
+
+            let position = self.axis_position(axis).await?;
+            *distance = position.abs_diff(*target);
+        }
+
+        let longest = distances.iter().copied().max().unwrap_or(0);
+        if longest == 0 {
+            return Ok(());
+        }
+
+        // Scaling speed and acceleration by the same ratio as the distance gives every axis the
+        // same profile shape, and therefore the same duration
+        for (axis, distance) in axes.iter().zip(distances) {
+            let scale = |v: u16| {
+                let scaled = (v as u64 * distance as u64).div_ceil(longest as u64);
+                (scaled as u16).max(1)
+            };
+
+            let rpm = scale(params.target_rpm);
+            let acceleration = scale(params.acceleration);
+            debug!(
+                "Axis {}: distance {}, rpm {}, acceleration {}",
+                axis.address, distance, rpm, acceleration
+            );
+
+            self.forget_axis(axis, 0x02, 2);
+            self.write_register(axis.address, 0x02, rpm).await?;
+            self.write_register(axis.address, 0x03, acceleration)
+                .await?;
+        }
+
+        match params.trigger {
+            Trigger::BroadcastEnableAll => {
+                for (axis, target) in axes.iter().zip(targets) {
+                    self.write_register(axis.address, 0x01, 0).await?;
+                    self.write_registers(axis.address, 0x0C, &position_to_raw(target))
+                        .await?;
+                }
+
+                self.broadcast_one_word_parameter(0x01, true, |v| match v {
+                    false => Ok(0),
+                    true => Ok(1),
+                })
+                .await?;
+            }
+            Trigger::Sequential => {
+                for (axis, target) in axes.iter().zip(targets) {
+                    self.write_registers(axis.address, 0x0C, &position_to_raw(target))
+                        .await?;
+                }
+            }
+        }
+
+        // Checked between transactions rather than with `with_timeout`, so that a slow axis does
+        // not cancel a transaction part way through
+        let deadline = Instant::now() + params.timeout;
+
+        'wait: loop {
+            if Instant::now() >= deadline {
+                return Err(Error::Timeout);
+            }
+
+            Timer::after(params.poll_interval).await;
+
+            for (axis, target) in axes.iter().zip(targets) {
+                let position = self.axis_position(axis).await?;
+
+                if position.abs_diff(target) > params.tolerance {
+                    continue 'wait;
+                }
+            }
+
+            return Ok(());
+        }
+    }
+
+    /// Gets the absolute position of the motor of an axis
+    async fn axis_position(&mut self, axis: &AxisMove) -> Result<u32> {
+        let mut raw = [0u16; 2];
+        self.read_registers(axis.address, 0x16, &mut raw).await?;
+        Ok(position_from_raw(raw[0], raw[1]))
+    }
+
+    // Writes to an axis bypass the cache, which must not keep the old values if it is this motor
+    fn forget_axis(&mut self, axis: &AxisMove, register: u16, count: usize) {
+        if axis.address == self.address {
+            self.cache.forget(register, count);
+        }
+    }
+}
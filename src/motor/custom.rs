@@ -0,0 +1,92 @@
+use super::Motor;
+use crate::{Error, Result};
+use modbus_core::{FunctionCode, Request, RequestPdu, Response};
+
+// Vendor specific function codes
+const TARGET_POSITION: u8 = 0x78;
+const ABSOLUTE_POSITION: u8 = 0x7B;
+
+/// How the data of a response to a custom function code is checked.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EchoCheck {
+    /// The response data is not checked
+    None,
+
+    /// The response data must be identical to the request data
+    Exact,
+}
+
+impl<I: embedded_io_async::Read + embedded_io_async::Write, const N: usize> Motor<I, N> {
+    /// Sends a request with a vendor specific function code, returning the response data
+    ///
+    /// Standard Modbus function codes, and codes with the exception bit set, are rejected with
+    /// [`Error::InvalidArgument`].
+    /// So are the position commands 0x78 and 0x7B, which must be sent with
+    /// [`Motor::set_target_position_custom`] and [`Motor::set_absolute_position_custom`] so that
+    /// they are checked against the soft limits.
+    pub async fn custom_command<'a>(
+        &'a mut self,
+        function: u8,
+        data: &'a [u8],
+        check: EchoCheck,
+    ) -> Result<&'a [u8]> {
+        if matches!(function, TARGET_POSITION | ABSOLUTE_POSITION) {
+            return Err(Error::InvalidArgument);
+        }
+
+        self.send_custom(function, data, check).await
+    }
+
+    async fn send_custom<'a>(
+        &'a mut self,
+        function: u8,
+        data: &'a [u8],
+        check: EchoCheck,
+    ) -> Result<&'a [u8]> {
+        let fc = FunctionCode::new(function);
+        if fc != FunctionCode::Custom(function) || function & 0x80 != 0 {
+            return Err(Error::InvalidArgument);
+        }
+
+        match function {
+            // The target and absolute position, neither of which is cached
+            TARGET_POSITION => self.cache.forget(0x0C, 2),
+            ABSOLUTE_POSITION => self.cache.forget(0x16, 2),
+            // The effect of any other function code on the registers is not known
+            _ => self.cache.invalidate(),
+        }
+
+        let request = RequestPdu(Request::Custom(fc, data));
+
+        match self.modbus_transaction(self.address, request).await? {
+            Response::Custom(f, d) if f == fc => match check {
+                EchoCheck::Exact if d != data => Err(Error::UnexpectedResponseData),
+                _ => Ok(d),
+            },
+            _ => Err(Error::UnexpectedResponseType),
+        }
+    }
+
+    /// Sets the target position using the custom function code 0x78
+    ///
+    /// The position is checked against the soft limits, see [`Motor::set_soft_limits`].
+    pub async fn set_target_position_custom(&mut self, value: u32) -> Result<()> {
+        let value = self.limit_position(value)?;
+
+        self.send_custom(TARGET_POSITION, &value.to_be_bytes(), EchoCheck::Exact)
+            .await?;
+        Ok(())
+    }
+
+    /// Sets the absolute position using the custom function code 0x7B
+    ///
+    /// The position is checked against the soft limits, see [`Motor::set_soft_limits`].
+    pub async fn set_absolute_position_custom(&mut self, value: u32) -> Result<()> {
+        let value = self.limit_position(value)?;
+
+        self.send_custom(ABSOLUTE_POSITION, &value.to_be_bytes(), EchoCheck::Exact)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,267 @@
+use super::Motor;
+use crate::{Direction, Error, Result, StopMode};
+use embassy_time::{Duration, Instant, Timer};
+
+// Interval between reading the speed while waiting for the motor to stop
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Speed below which the motor is taken to have stopped, as it may not read exactly 0 while
+// holding its position
+const STOPPED_RPM: f32 = 1.;
+
+pub(super) struct JogState {
+    timeout: Duration,
+    stop_mode: StopMode,
+    stop_wait: Duration,
+
+    // Direction, speed and deadline of the jog in progress
+    active: Option<(Direction, u16, Instant)>,
+
+    // Direction polarity before the jog started, restored when it is stopped.
+    // Kept after a stop that did not complete, until the polarity has been restored.
+    polarity: Option<Direction>,
+}
+
+impl Default for JogState {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_millis(250),
+            stop_mode: StopMode::ZeroSpeed,
+            stop_wait: Duration::from_secs(2),
+            active: None,
+            polarity: None,
+        }
+    }
+}
+
+impl<I: embedded_io_async::Read + embedded_io_async::Write, const N: usize> Motor<I, N> {
+    /// Sets how long a jog continues without being refreshed before it is stopped
+    ///
+    /// Defaults to 250ms.
+    pub fn set_jog_timeout(&mut self, timeout: Duration) {
+        self.jog.timeout = timeout;
+    }
+
+    /// Sets how a jog is stopped, both explicitly and by the deadman timeout
+    ///
+    /// Defaults to [`StopMode::ZeroSpeed`].
+    pub fn set_jog_stop_mode(&mut self, mode: StopMode) {
+        self.jog.stop_mode = mode;
+    }
+
+    /// Sets how long to wait for the motor to stop before reversing it, or restoring its
+    /// direction polarity at the end of a jog
+    ///
+    /// Defaults to 2s.
+    pub fn set_jog_stop_wait(&mut self, wait: Duration) {
+        self.jog.stop_wait = wait;
+    }
+
+    /// Runs the motor at a speed in a direction, or refreshes a jog that is already running
+    ///
+    /// The motor must already be in speed mode.
+    /// The direction is set with the direction polarity, which is restored when the jog is
+    /// stopped.
+    /// Fails with [`Error::InvalidArgument`] if the polarity has to be changed while the parameter
+    /// save flag is set, as the motor would keep the inverted polarity after a power cycle.
+    /// The save flag must not be set while a jog is running for the same reason.
+    /// The jog must be refreshed by calling this again within the jog timeout, otherwise it is
+    /// stopped by [`Motor::jog_deadman`] or [`Motor::check_jog`].
+    /// Refreshing with an unchanged direction and speed does not communicate with the motor.
+    pub async fn jog(&mut self, direction: Direction, rpm: u16) -> Result<()> {
+        let deadline = Instant::now() + self.jog.timeout;
+
+        match self.jog.active {
+            Some((d, r, _)) if d == direction && r == rpm => {}
+            Some((d, _, _)) if d == direction => {
+                self.set_target_rpm(rpm).await?;
+            }
+            active => {
+                // A jog is running, or the last one was not completely stopped, so the motor may
+                // be moving with its polarity changed
+                let unfinished = active.is_some() || self.jog.polarity.is_some();
+
+                let polarity = match self.jog.polarity {
+                    Some(polarity) => polarity,
+                    None => self.dir_polarity().await?,
+                };
+
+                let change_polarity = unfinished || direction != polarity;
+                if change_polarity && self.parameter_save_flag().await? {
+                    warn!("Not jogging by changing the polarity while parameters are saved");
+                    return Err(Error::InvalidArgument);
+                }
+                self.jog.polarity = Some(polarity);
+
+                if unfinished {
+                    // Do not reverse the motor while it is running
+                    self.set_target_rpm(0).await?;
+                    self.wait_until_stopped().await?;
+                }
+
+                if change_polarity {
+                    self.set_dir_polarity(direction).await?;
+                }
+                self.set_target_rpm(rpm).await?;
+                self.set_drive_enabled(true).await?;
+            }
+        }
+
+        self.jog.active = Some((direction, rpm, deadline));
+
+        Ok(())
+    }
+
+    /// Stops a jog, then restores the direction polarity once the motor has stopped
+    ///
+    /// The jog is no longer running once this has started, even if it fails or is cancelled.
+    /// A stop that did not complete is not retried by [`Motor::jog_deadman`], but by calling this
+    /// again, or by the next jog.
+    pub async fn stop_jog(&mut self) -> Result<()> {
+        debug!("Stopping jog");
+
+        // A jog in the original direction has no polarity to restore
+        if let Some((direction, _, _)) = self.jog.active.take()
+            && self.jog.polarity == Some(direction)
+        {
+            self.jog.polarity = None;
+        }
+
+        match self.jog.stop_mode {
+            StopMode::ZeroSpeed => self.set_target_rpm(0).await?,
+            StopMode::DisableDrive => self.set_drive_enabled(false).await?,
+        }
+
+        if let Some(polarity) = self.jog.polarity {
+            self.wait_until_stopped().await?;
+            self.set_dir_polarity(polarity).await?;
+            self.jog.polarity = None;
+        }
+
+        Ok(())
+    }
+
+    /// Stops a jog that has not been refreshed within the jog timeout
+    ///
+    /// Returns `true` if the jog was stopped.
+    pub async fn check_jog(&mut self) -> Result<bool> {
+        match self.jog.active {
+            Some((_, _, deadline)) if Instant::now() >= deadline => {
+                warn!("Jog was not refreshed in time");
+                self.stop_jog().await?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Waits for a running jog to expire, then stops it
+    ///
+    /// Never completes if there is no jog running.
+    /// This is intended to be raced (e.g. with `select`) against receiving jog commands in the
+    /// task that owns the motor, so that the motor is stopped when the task sending the commands
+    /// stops doing so.
+    pub async fn jog_deadman(&mut self) -> Result<()> {
+        loop {
+            match self.jog.active {
+                Some((_, _, deadline)) => {
+                    Timer::at(deadline).await;
+
+                    if self.check_jog().await? {
+                        return Ok(());
+                    }
+                }
+                None => core::future::pending().await,
+            }
+        }
+    }
+
+    async fn wait_until_stopped(&mut self) -> Result<()> {
+        let deadline = Instant::now() + self.jog.stop_wait;
+
+        while self.speed().await? >= STOPPED_RPM {
+            if Instant::now() >= deadline {
+                warn!("Motor did not stop in time");
+                return Err(Error::Timeout);
+            }
+            Timer::after(STOP_POLL_INTERVAL).await;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::testing::{TestBus, motor},
+        *,
+    };
+    use embassy_futures::block_on;
+
+    // Jogs clockwise, against the motor's counter clockwise polarity
+    fn reversed_jog(configure: impl FnOnce(&mut TestBus)) -> Motor<TestBus> {
+        let mut bus = TestBus::new();
+        bus.registers[0x09] = 0;
+        configure(&mut bus);
+
+        let mut motor = motor(bus);
+        block_on(motor.jog(Direction::Clockwise, 100)).unwrap();
+        assert_eq!(motor.transport().registers[0x09], 1);
+        motor
+    }
+
+    #[test]
+    fn stop_restores_polarity_when_speed_is_not_exactly_zero() {
+        let mut motor = reversed_jog(|bus| bus.registers[0x10] = 5);
+
+        assert_eq!(block_on(motor.stop_jog()), Ok(()));
+        assert_eq!(motor.transport().registers[0x02], 0);
+        assert_eq!(motor.transport().registers[0x09], 0);
+    }
+
+    #[test]
+    fn failed_stop_does_not_leave_jog_running() {
+        let mut motor = reversed_jog(|_| {});
+
+        motor.transport_mut().silent = 1;
+        assert_eq!(block_on(motor.stop_jog()), Err(Error::Timeout));
+        assert_eq!(block_on(motor.check_jog()), Ok(false));
+        assert_eq!(motor.transport().registers[0x09], 1);
+
+        assert_eq!(block_on(motor.stop_jog()), Ok(()));
+        assert_eq!(motor.transport().registers[0x09], 0);
+    }
+
+    #[test]
+    fn next_jog_after_failed_stop_sets_polarity() {
+        let mut motor = reversed_jog(|_| {});
+
+        motor.transport_mut().silent = 1;
+        assert_eq!(block_on(motor.stop_jog()), Err(Error::Timeout));
+
+        block_on(motor.jog(Direction::CounterClockwise, 100)).unwrap();
+        assert_eq!(motor.transport().registers[0x09], 0);
+        assert_eq!(motor.transport().registers[0x02], 100);
+    }
+
+    #[test]
+    fn polarity_is_not_changed_while_parameters_are_saved() {
+        let mut bus = TestBus::new();
+        bus.registers[0x14] = 1;
+        let mut motor = motor(bus);
+
+        assert_eq!(
+            block_on(motor.jog(Direction::Clockwise, 100)),
+            Err(Error::InvalidArgument)
+        );
+        assert_eq!(motor.transport().registers[0x09], 0);
+        assert_eq!(motor.transport().registers[0x01], 0);
+
+        // Jogging in the saved direction leaves the polarity alone
+        assert_eq!(
+            block_on(motor.jog(Direction::CounterClockwise, 100)),
+            Ok(())
+        );
+    }
+}
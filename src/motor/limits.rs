@@ -0,0 +1,109 @@
+use super::Motor;
+use crate::{Error, Result};
+
+/// What happens to a position command that is outside of the [`SoftLimits`].
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitAction {
+    /// The command is not sent and [`Error::LimitViolation`] is returned
+    Reject,
+
+    /// The nearest limit is sent instead of the commanded position
+    Clamp,
+}
+
+/// Range of positions that the motor may be commanded to.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SoftLimits {
+    pub min: u32,
+    pub max: u32,
+    pub action: LimitAction,
+}
+
+impl SoftLimits {
+    pub fn contains(&self, position: u32) -> bool {
+        (self.min..=self.max).contains(&position)
+    }
+}
+
+/// Record of a position command that was outside of the [`SoftLimits`].
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LimitEvent {
+    /// The position that was requested
+    pub requested: u32,
+
+    /// The position that was sent instead, `None` if the command was rejected
+    pub commanded: Option<u32>,
+}
+
+#[derive(Default)]
+pub(super) struct LimitState {
+    soft_limits: Option<SoftLimits>,
+    last_event: Option<LimitEvent>,
+}
+
+impl<I: embedded_io_async::Read + embedded_io_async::Write, const N: usize> Motor<I, N> {
+    /// Sets the soft limits that every position command is checked against
+    ///
+    /// This covers [`Motor::set_target_position`], [`Motor::set_absolute_position`],
+    /// [`Motor::set_target_position_custom`], [`Motor::set_absolute_position_custom`] and
+    /// everything built on them (e.g. trajectory setpoints).
+    /// Coordinated moves check each axis against its own limits instead, see [`AxisMove`](crate::AxisMove).
+    /// `None` removes the limits.
+    pub fn set_soft_limits(&mut self, limits: Option<SoftLimits>) -> Result<()> {
+        if limits.as_ref().is_some_and(|l| l.min > l.max) {
+            return Err(Error::InvalidArgument);
+        }
+
+        self.limits.soft_limits = limits;
+        Ok(())
+    }
+
+    pub fn soft_limits(&self) -> Option<&SoftLimits> {
+        self.limits.soft_limits.as_ref()
+    }
+
+    /// Gets the most recent soft limit violation, if there has been one since this was last called
+    pub fn take_limit_event(&mut self) -> Option<LimitEvent> {
+        self.limits.last_event.take()
+    }
+
+    /// Checks a position command against the soft limits, giving the position to send
+    pub(super) fn limit_position(&mut self, position: u32) -> Result<u32> {
+        let limits = self.limits.soft_limits.clone();
+        self.check_position(limits.as_ref(), position)
+    }
+
+    /// Checks a position command against the given soft limits, giving the position to send
+    ///
+    /// Violations are recorded as for the soft limits of this motor, see
+    /// [`Motor::take_limit_event`].
+    pub(super) fn check_position(
+        &mut self,
+        limits: Option<&SoftLimits>,
+        position: u32,
+    ) -> Result<u32> {
+        let Some(limits) = limits else {
+            return Ok(position);
+        };
+
+        if limits.contains(position) {
+            return Ok(position);
+        }
+
+        let event = LimitEvent {
+            requested: position,
+            commanded: match limits.action {
+                LimitAction::Reject => None,
+                LimitAction::Clamp => Some(position.clamp(limits.min, limits.max)),
+            },
+        };
+        warn!("Soft limit violation: {:?}", event);
+
+        self.limits.last_event = Some(event);
+
+        event.commanded.ok_or(Error::LimitViolation(position))
+    }
+}
@@ -1,14 +1,55 @@
+mod alarm;
+#[cfg(feature = "alarm-history")]
+mod alarm_history;
+mod batch;
+mod cache;
+mod coordinated;
+mod custom;
+mod jog;
+mod limits;
 mod parameters;
-
-use crate::{Error, Result, RtuBaud};
-use defmt::debug;
+mod protocol;
+mod receive;
+mod scan;
+#[cfg(test)]
+mod testing;
+#[cfg(feature = "timing")]
+mod timing;
+mod trajectory;
+
+#[cfg(feature = "alarm-history")]
+pub use alarm_history::{AlarmHistory, AlarmRecord};
+pub use batch::{WriteBatch, WriteReport};
+pub use coordinated::{AxisMove, CoordinatedMove, Trigger};
+pub use custom::EchoCheck;
+pub use limits::{LimitAction, LimitEvent, SoftLimits};
+pub use protocol::Protocol;
+pub use receive::ReceiveMode;
+pub use scan::{DiscoveredMotor, ScanProgress, ScanSummary, scan};
+#[cfg(feature = "timing")]
+pub use timing::{FunctionTiming, HISTOGRAM_BOUNDS, PhaseStats, TimingStats, TransactionTiming};
+pub use trajectory::{SetpointCommand, TrackingReport};
+
+use crate::{Error, Result, RtuBaud, SerialConfig, SetBaudRate, fmt::Hex};
+use core::ops::Range;
 use embassy_time::{Duration, Instant, Timer, with_timeout};
 use modbus_core::{
-    Data, FunctionCode, Request, RequestPdu, Response,
-    rtu::{Header, RequestAdu},
+    ExceptionResponse, Request, RequestPdu, Response, ResponsePdu,
+    rtu::{Header, RequestAdu, crc16},
 };
 
-pub struct Motor<I: embedded_io_async::Read + embedded_io_async::Write> {
+const BROADCAST_ADDRESS: u8 = 0;
+
+// Values written to register 0x00 to unlock, and then save, the communication parameters
+const UNLOCK: u16 = 1;
+const SAVE: u16 = 506;
+
+/// A motor on a Modbus bus.
+///
+/// `N` is the size of the buffer used for requests and responses, which limits how many
+/// registers can be read or written in a single request.
+/// See [`Motor::with_buffer_size`] to change it.
+pub struct Motor<I: embedded_io_async::Read + embedded_io_async::Write, const N: usize = 64> {
     comm: I,
 
     t15: Duration,
@@ -16,57 +57,238 @@ pub struct Motor<I: embedded_io_async::Read + embedded_io_async::Write> {
     response_timeout: Duration,
 
     address: u8,
+    serial: SerialConfig,
+    protocol: Protocol,
+    transaction_id: u16,
 
-    buffer: [u8; 64],
+    buffer: [u8; N],
     earliest_next_frame: Instant,
+
+    #[cfg(feature = "alarm-history")]
+    alarms: alarm_history::AlarmHistory,
+    cache: cache::RegisterCache,
+    jog: jog::JogState,
+    limits: limits::LimitState,
+    receive: receive::ReceiveState,
+    #[cfg(feature = "timing")]
+    timing: timing::TimingStats,
 }
 
 impl<I: embedded_io_async::Read + embedded_io_async::Write> Motor<I> {
-    pub fn new(comm: I, baud: RtuBaud, address: u8, response_timeout: Duration) -> Self {
+    /// Creates a motor, accepting either an [`RtuBaud`] (8N1) or a [`SerialConfig`]
+    pub fn new(
+        comm: I,
+        serial: impl Into<SerialConfig>,
+        address: u8,
+        response_timeout: Duration,
+    ) -> Self {
+        let serial = serial.into();
+
         Self {
             comm,
-            t15: baud.t15(),
-            t35: baud.t35(),
+            t15: serial.t15(),
+            t35: serial.t35(),
             response_timeout,
             address,
+            serial,
+            protocol: Protocol::Rtu,
+            transaction_id: 0,
             buffer: [0u8; 64],
             earliest_next_frame: Instant::now(),
+            #[cfg(feature = "alarm-history")]
+            alarms: Default::default(),
+            cache: Default::default(),
+            jog: Default::default(),
+            limits: Default::default(),
+            receive: Default::default(),
+            #[cfg(feature = "timing")]
+            timing: Default::default(),
+        }
+    }
+}
+
+impl<I: embedded_io_async::Read + embedded_io_async::Write, const N: usize> Motor<I, N> {
+    /// Changes the size of the buffer used for requests and responses
+    ///
+    /// Later requests fail with [`Error::BufferTooSmall`] if they, or their responses, do not fit.
+    pub fn with_buffer_size<const M: usize>(self) -> Motor<I, M> {
+        Motor {
+            comm: self.comm,
+            t15: self.t15,
+            t35: self.t35,
+            response_timeout: self.response_timeout,
+            address: self.address,
+            serial: self.serial,
+            protocol: self.protocol,
+            transaction_id: self.transaction_id,
+            buffer: [0u8; M],
+            earliest_next_frame: self.earliest_next_frame,
+            #[cfg(feature = "alarm-history")]
+            alarms: self.alarms,
+            cache: self.cache,
+            jog: self.jog,
+            limits: self.limits,
+            receive: self.receive,
+            #[cfg(feature = "timing")]
+            timing: self.timing,
         }
     }
 
-    async fn modbus_transaction<'a>(&'a mut self, req: RequestPdu<'a>) -> Result<Response<'a>> {
+    /// Gets the Modbus address of the motor
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// Gets the serial configuration used to communicate with the motor
+    pub fn serial_config(&self) -> SerialConfig {
+        self.serial
+    }
+
+    /// Gets the transport used to communicate with the motor
+    pub fn transport(&self) -> &I {
+        &self.comm
+    }
+
+    /// Gets the transport used to communicate with the motor
+    ///
+    /// Reading from or writing to the transport directly can corrupt the next transaction.
+    pub fn transport_mut(&mut self) -> &mut I {
+        &mut self.comm
+    }
+
+    /// Sends a request to the motor at `unit`, and receives the response
+    ///
+    /// The unit is passed explicitly rather than changing the address of this [`Motor`], so that
+    /// a cancelled transaction cannot leave it addressing another motor.
+    async fn modbus_transaction<'a>(
+        &'a mut self,
+        unit: u8,
+        req: RequestPdu<'a>,
+    ) -> Result<Response<'a>> {
+        self.transaction(unit, |m| m.encode_request(unit, req))
+            .await
+    }
+
+    /// Sends a request, encoded into the buffer by `encode`, and receives the response
+    async fn transaction(
+        &mut self,
+        unit: u8,
+        encode: impl FnOnce(&mut Self) -> Result<usize>,
+    ) -> Result<Response<'_>> {
+        #[cfg(feature = "timing")]
+        let waiting = Instant::now();
+
         // Ensure we wait for at least the inter-frame delay
         Timer::at(self.earliest_next_frame).await;
 
-        // Create request
-        let request = RequestAdu {
-            hdr: Header {
-                slave: self.address,
-            },
-            pdu: req,
-        };
+        // Discard anything that arrived late for an earlier transaction
+        while let Ok(Ok(n @ 1..)) =
+            with_timeout(Duration::from_ticks(0), self.comm.read(&mut self.buffer)).await
+        {
+            debug!("Discarded {} late bytes", n);
+        }
 
         // Encode request
-        let n = modbus_core::rtu::client::encode_request(request, &mut self.buffer)
-            .map_err(|_| Error::Transport)?;
+        let n = encode(self)?;
         let data = &self.buffer[..n];
-        debug!("Encoded request: ({}) {:x}", n, data);
+        debug!("Encoded request: ({}) {}", n, Hex(data));
+        let function = match self.protocol {
+            Protocol::Tcp => data[protocol::MBAP_LEN],
+            Protocol::Rtu | Protocol::RtuOverTcp => data[1],
+        };
 
         // Send request
+        let started = Instant::now();
         self.comm
             .write_all(data)
             .await
             .map_err(|_| Error::Transport)?;
 
+        #[cfg(feature = "timing")]
+        let sent = Instant::now();
+
+        self.receive.first_byte = None;
+        let frame = match self.protocol {
+            Protocol::Tcp => self.receive_tcp(started, unit).await?,
+            Protocol::Rtu | Protocol::RtuOverTcp => {
+                self.receive_rtu(started, unit, function, n).await?
+            }
+        };
+
+        let finished = Instant::now();
+        self.receive.last_transaction_time = Some(finished - started);
+
+        #[cfg(feature = "timing")]
+        if let Some(first_byte) = self.receive.first_byte {
+            self.timing.record(
+                function,
+                timing::TransactionTiming {
+                    wait: started - waiting,
+                    transmit: sent - started,
+                    first_byte: first_byte - sent,
+                    completion: finished - first_byte,
+                },
+            );
+        }
+
+        let data = &self.buffer[frame];
+
+        // Try to parse the response
+        let response = match self.protocol {
+            Protocol::Tcp => decode_pdu(&data[protocol::MBAP_LEN..])?,
+            Protocol::Rtu | Protocol::RtuOverTcp => {
+                if data[0] != unit {
+                    return Err(Error::UnexpectedResponseData);
+                }
+
+                match modbus_core::rtu::client::decode_response(data)
+                    .map_err(|_| Error::Transport)?
+                {
+                    Some(response) => response.pdu,
+                    None => decode_custom_response(data)?,
+                }
+            }
+        };
+
+        response.0.map_err(|_| Error::Modbus)
+    }
+
+    /// Receives an RTU response, returning where it is in the buffer
+    async fn receive_rtu(
+        &mut self,
+        started: Instant,
+        unit: u8,
+        function: u8,
+        request_len: usize,
+    ) -> Result<Range<usize>> {
+        let by_length =
+            self.receive.mode == ReceiveMode::FrameLength && receive::has_known_length(function);
+        let skip = if self.receive.local_echo {
+            request_len
+        } else {
+            0
+        };
+        let deadline = started + self.response_timeout;
+
         let mut timeout = self.response_timeout;
         let mut total_read = 0;
+        let mut frame = None;
 
         // Receive data
         'rx: loop {
+            if total_read == self.buffer.len() {
+                break 'rx;
+            }
+
             match with_timeout(timeout, self.comm.read(&mut self.buffer[total_read..])).await {
                 Ok(Ok(n)) => {
                     total_read += n;
                     self.earliest_next_frame = Instant::now() + self.t35;
+
+                    // The local echo is not part of the response
+                    if total_read > skip {
+                        self.receive.first_byte.get_or_insert(Instant::now());
+                    }
                 }
                 Ok(Err(_)) => {
                     return Err(Error::Transport);
@@ -74,64 +296,297 @@ impl<I: embedded_io_async::Read + embedded_io_async::Write> Motor<I> {
                 Err(_) => break 'rx,
             }
 
-            timeout = self.t15;
+            if total_read > skip {
+                let received = &self.buffer[skip..total_read];
+
+                // Exceptions can always be found by length, even if the normal response cannot
+                if self.receive.mode == ReceiveMode::FrameLength {
+                    frame = receive::find_frame(received, unit, function);
+                }
+
+                // Over TCP there is no gap to end a frame, but there is no noise to fake a CRC
+                if self.protocol == Protocol::RtuOverTcp && frame.is_none() {
+                    frame = receive::find_unsized_frame(received, unit, function);
+                }
+
+                if frame.is_some() {
+                    break 'rx;
+                }
+            }
+
+            timeout = if by_length || self.protocol == Protocol::RtuOverTcp || total_read <= skip {
+                // The UART may deliver the frame in bursts, so wait for the rest of it, and the
+                // response may not have started if only the echo has been received
+                deadline.saturating_duration_since(Instant::now())
+            } else {
+                self.t15
+            };
         }
 
-        if total_read == 0 {
+        if total_read <= skip {
             // Timeout if nothing has been received
-            Err(Error::Timeout)
-        } else {
-            let data = &self.buffer[..total_read];
-            debug!("Received: ({}) {:x}", total_read, data);
+            return Err(Error::Timeout);
+        }
+
+        let received = &self.buffer[skip..total_read];
+        debug!("Received: ({}) {}", received.len(), Hex(received));
+
+        let frame = match frame
+            .or_else(|| receive::find_frame(received, unit, function))
+            .or_else(|| receive::find_unsized_frame(received, unit, function))
+        {
+            Some(frame) => frame,
+            None if total_read == self.buffer.len() => return Err(Error::BufferTooSmall),
+            None => 0..received.len(),
+        };
+
+        Ok(frame.start + skip..frame.end + skip)
+    }
 
-            // Try to parse the response
-            let response = modbus_core::rtu::client::decode_response(data)
-                .map_err(|_| Error::Transport)?
-                .ok_or(Error::Modbus)?;
+    /// Receives a Modbus TCP response, returning where it is in the buffer
+    async fn receive_tcp(&mut self, started: Instant, unit: u8) -> Result<Range<usize>> {
+        let deadline = started + self.response_timeout;
+        let mut total_read = 0;
 
-            Ok(response.pdu.0.map_err(|_| Error::Modbus)?)
+        loop {
+            if total_read == self.buffer.len() {
+                return Err(Error::BufferTooSmall);
+            }
+
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match with_timeout(timeout, self.comm.read(&mut self.buffer[total_read..])).await {
+                Ok(Ok(0)) => return Err(Error::Transport),
+                Ok(Ok(n)) => {
+                    total_read += n;
+                    self.receive.first_byte.get_or_insert(Instant::now());
+                }
+                Ok(Err(_)) => return Err(Error::Transport),
+                Err(_) if total_read == 0 => return Err(Error::Timeout),
+                Err(_) => {
+                    debug!(
+                        "Incomplete: ({}) {}",
+                        total_read,
+                        Hex(&self.buffer[..total_read])
+                    );
+                    return Err(Error::UnexpectedResponseData);
+                }
+            }
+
+            if let Some(frame) =
+                protocol::find_tcp_frame(&self.buffer[..total_read], self.transaction_id, unit)
+            {
+                debug!(
+                    "Received: ({}) {}",
+                    total_read,
+                    Hex(&self.buffer[..total_read])
+                );
+                return Ok(frame);
+            }
         }
     }
 
+    /// Encodes a request into the buffer, returning its length
+    fn encode_request(&mut self, slave: u8, req: RequestPdu<'_>) -> Result<usize> {
+        match self.protocol {
+            Protocol::Tcp => {
+                self.transaction_id = self.transaction_id.wrapping_add(1);
+
+                let request = modbus_core::tcp::RequestAdu {
+                    hdr: modbus_core::tcp::Header {
+                        transaction_id: self.transaction_id,
+                        unit_id: slave,
+                    },
+                    pdu: req,
+                };
+                modbus_core::tcp::client::encode_request(request, &mut self.buffer)
+            }
+            Protocol::Rtu | Protocol::RtuOverTcp => {
+                let request = RequestAdu {
+                    hdr: Header { slave },
+                    pdu: req,
+                };
+                modbus_core::rtu::client::encode_request(request, &mut self.buffer)
+            }
+        }
+        .map_err(|_| Error::BufferTooSmall)
+    }
+
+    /// Sends a request to all motors on the bus, no response is expected
+    async fn modbus_broadcast(&mut self, req: RequestPdu<'_>) -> Result<()> {
+        // Ensure we wait for at least the inter-frame delay
+        Timer::at(self.earliest_next_frame).await;
+
+        // Encode request
+        let n = self.encode_request(BROADCAST_ADDRESS, req)?;
+        let data = &self.buffer[..n];
+        debug!("Encoded broadcast request: ({}) {}", n, Hex(data));
+
+        // Send request, waiting until it has actually been transmitted
+        self.comm
+            .write_all(data)
+            .await
+            .map_err(|_| Error::Transport)?;
+        self.comm.flush().await.map_err(|_| Error::Transport)?;
+
+        self.earliest_next_frame = Instant::now() + self.t35;
+
+        Ok(())
+    }
+
+    /// Waits until the motor responds, or the timeout expires
+    async fn wait_until_reachable(&mut self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+
+        while Instant::now() < deadline {
+            match self.device_address().await {
+                Ok(_) => return true,
+                Err(e) => {
+                    debug!("Motor not reachable yet: {}", e);
+                    Timer::after(self.response_timeout).await;
+                }
+            }
+        }
+
+        false
+    }
+
     async fn read_one_word_parameter<T, F>(&mut self, address: u16, transform: F) -> Result<T>
     where
         F: Fn(u16) -> Result<T>,
     {
-        let request = RequestPdu(Request::ReadHoldingRegisters(address, 1));
+        let mut raw = [0u16; 1];
+        self.read_parameters(address, &mut raw).await?;
+        transform(raw[0])
+    }
+
+    async fn read_two_word_parameter<T, F>(&mut self, address: u16, transform: F) -> Result<T>
+    where
+        F: Fn(u16, u16) -> Result<T>,
+    {
+        let mut raw = [0u16; 2];
+        self.read_parameters(address, &mut raw).await?;
+        transform(raw[0], raw[1])
+    }
+
+    async fn read_parameters(&mut self, address: u16, values: &mut [u16]) -> Result<()> {
+        if self.cache.get(self.address, address, values) {
+            return Ok(());
+        }
+
+        let result = self.read_registers(self.address, address, values).await;
+        match result {
+            Ok(()) => self.cache.store(self.address, address, values),
+            Err(_) => self.cache.invalidate(),
+        }
+        result
+    }
 
-        match self.modbus_transaction(request).await? {
+    async fn read_registers(&mut self, unit: u8, address: u16, values: &mut [u16]) -> Result<()> {
+        // Check the response will fit before sending the request
+        let overhead = match self.protocol {
+            // MBAP header, function code and byte count
+            Protocol::Tcp => protocol::MBAP_LEN + 2,
+            // Address, function code, byte count and CRC
+            Protocol::Rtu | Protocol::RtuOverTcp => 1 + 2 + 2,
+        };
+        if overhead + values.len() * 2 > self.buffer.len() {
+            return Err(Error::BufferTooSmall);
+        }
+
+        let request = RequestPdu(Request::ReadHoldingRegisters(address, values.len() as u16));
+
+        match self.modbus_transaction(unit, request).await? {
             Response::ReadHoldingRegisters(data) => {
-                if data.len() == 1 {
-                    let raw = data.get(0).unwrap();
-                    Ok(transform(raw)?)
+                if data.len() == values.len() {
+                    for (i, value) in values.iter_mut().enumerate() {
+                        *value = data.get(i).unwrap();
+                    }
+                    Ok(())
                 } else {
-                    Err(Error::UnexpectedResponseLength(data.len(), 1))
+                    Err(Error::UnexpectedResponseLength(data.len(), values.len()))
                 }
             }
             _ => Err(Error::UnexpectedResponseType),
         }
     }
 
-    async fn read_two_word_parameter<T, F>(&mut self, address: u16, transform: F) -> Result<T>
-    where
-        F: Fn(u16, u16) -> Result<T>,
-    {
-        let request = RequestPdu(Request::ReadHoldingRegisters(address, 2));
+    async fn write_parameters(&mut self, address: u16, values: &[u16]) -> Result<()> {
+        if self.cache.holds(self.address, address, values) {
+            return Ok(());
+        }
 
-        match self.modbus_transaction(request).await? {
-            Response::ReadHoldingRegisters(data) => {
-                if data.len() == 2 {
-                    let raw_0 = data.get(0).unwrap();
-                    let raw_1 = data.get(1).unwrap();
-                    Ok(transform(raw_0, raw_1)?)
+        let result = self.write_registers(self.address, address, values).await;
+        match result {
+            Ok(()) => self.cache.store(self.address, address, values),
+            Err(_) => self.cache.invalidate(),
+        }
+        result
+    }
+
+    async fn write_registers(&mut self, unit: u8, address: u16, values: &[u16]) -> Result<()> {
+        match self
+            .transaction(unit, |m| m.encode_write_registers(unit, address, values))
+            .await?
+        {
+            Response::WriteMultipleRegisters(a, n) => {
+                if a == address && n as usize == values.len() {
+                    Ok(())
                 } else {
-                    Err(Error::UnexpectedResponseLength(data.len(), 2))
+                    Err(Error::UnexpectedResponseData)
                 }
             }
             _ => Err(Error::UnexpectedResponseType),
         }
     }
 
+    /// Encodes a request writing multiple registers into the buffer, returning its length
+    ///
+    /// modbus-core can only encode the values from a separate buffer, so they are written straight
+    /// into the frame here instead.
+    fn encode_write_registers(&mut self, unit: u8, address: u16, values: &[u16]) -> Result<usize> {
+        // Maximum number of registers in a single write request
+        if values.is_empty() || values.len() > 123 {
+            return Err(Error::InvalidArgument);
+        }
+
+        let (header, crc) = match self.protocol {
+            Protocol::Tcp => (protocol::MBAP_LEN, 0),
+            Protocol::Rtu | Protocol::RtuOverTcp => (1, 2),
+        };
+        // Function code, address, count and byte count, then the values
+        let pdu_len = 6 + values.len() * 2;
+        let n = header + pdu_len + crc;
+
+        let frame = self.buffer.get_mut(..n).ok_or(Error::BufferTooSmall)?;
+
+        let pdu = &mut frame[header..header + pdu_len];
+        pdu[0] = 0x10;
+        pdu[1..3].copy_from_slice(&address.to_be_bytes());
+        pdu[3..5].copy_from_slice(&(values.len() as u16).to_be_bytes());
+        pdu[5] = (values.len() * 2) as u8;
+        for (bytes, value) in pdu[6..].chunks_exact_mut(2).zip(values) {
+            bytes.copy_from_slice(&value.to_be_bytes());
+        }
+
+        match self.protocol {
+            Protocol::Tcp => {
+                self.transaction_id = self.transaction_id.wrapping_add(1);
+
+                frame[0..2].copy_from_slice(&self.transaction_id.to_be_bytes());
+                frame[2..4].copy_from_slice(&[0, 0]);
+                frame[4..6].copy_from_slice(&(1 + pdu_len as u16).to_be_bytes());
+                frame[6] = unit;
+            }
+            Protocol::Rtu | Protocol::RtuOverTcp => {
+                frame[0] = unit;
+                let crc = crc16(&frame[..n - 2]);
+                frame[n - 2..].copy_from_slice(&crc.to_be_bytes());
+            }
+        }
+
+        Ok(n)
+    }
+
     async fn write_one_word_parameter<T, F>(
         &mut self,
         address: u16,
@@ -143,9 +598,22 @@ impl<I: embedded_io_async::Read + embedded_io_async::Write> Motor<I> {
     {
         let data = transform(value)?;
 
+        if self.cache.holds(self.address, address, &[data]) {
+            return Ok(());
+        }
+
+        let result = self.write_register(self.address, address, data).await;
+        match result {
+            Ok(()) => self.cache.store(self.address, address, &[data]),
+            Err(_) => self.cache.invalidate(),
+        }
+        result
+    }
+
+    async fn write_register(&mut self, unit: u8, address: u16, data: u16) -> Result<()> {
         let request = RequestPdu(Request::WriteSingleRegister(address, data));
 
-        match self.modbus_transaction(request).await? {
+        match self.modbus_transaction(unit, request).await? {
             Response::WriteSingleRegister(a, d) => {
                 if a == address && d == data {
                     Ok(())
@@ -157,6 +625,25 @@ impl<I: embedded_io_async::Read + embedded_io_async::Write> Motor<I> {
         }
     }
 
+    async fn broadcast_one_word_parameter<T, F>(
+        &mut self,
+        address: u16,
+        value: T,
+        transform: F,
+    ) -> Result<()>
+    where
+        F: Fn(T) -> Result<u16>,
+    {
+        let data = transform(value)?;
+
+        // Every motor is written, but none confirm it
+        self.cache.forget(address, 1);
+
+        let request = RequestPdu(Request::WriteSingleRegister(address, data));
+
+        self.modbus_broadcast(request).await
+    }
+
     async fn write_two_word_parameter<T, F>(
         &mut self,
         address: u16,
@@ -167,73 +654,198 @@ impl<I: embedded_io_async::Read + embedded_io_async::Write> Motor<I> {
         F: Fn(T) -> Result<[u16; 2]>,
     {
         let data = transform(value)?;
+        self.write_parameters(address, &data).await
+    }
 
-        let mut buff = [0u8; 4];
-        let data = Data::from_words(&data, &mut buff).unwrap();
+    /// Writes and saves the baud rate of the motor
+    ///
+    /// The written values are read back before they are saved, failing with
+    /// [`Error::ReconfigurationFailed`] if the motor did not take them.
+    /// The motor keeps communicating at its current baud rate until it is power cycled.
+    /// See [`Motor::reconfigure_baud`] to also switch the transport over.
+    pub async fn set_baud_rate(&mut self, baud: RtuBaud) -> Result<()> {
+        // Once unlocked, registers 0x02 and 0x03 hold the communication parameters instead of
+        // the speed and acceleration. They are forgotten, and written around the cache, so that
+        // the cache never holds the communication parameters, whichever way this returns.
+        self.cache.forget(0x02, 2);
+
+        let values = [baud.register_value(), 129];
+
+        self.write_register(self.address, 0x00, UNLOCK).await?;
+        self.write_register(self.address, 0x02, values[0]).await?;
+        self.write_register(self.address, 0x03, values[1]).await?;
+
+        // Check the motor took the values before saving them
+        let mut written = [0u16; 2];
+        self.read_registers(self.address, 0x02, &mut written)
+            .await?;
+        if written != values {
+            warn!(
+                "Communication parameters read back as {:?}, expected {:?}",
+                written, values
+            );
+            return Err(Error::ReconfigurationFailed);
+        }
 
-        let request = RequestPdu(Request::WriteMultipleRegisters(address, data));
+        self.write_register(self.address, 0x00, SAVE).await
+    }
 
-        match self.modbus_transaction(request).await? {
-            Response::WriteMultipleRegisters(a, 2) => {
-                if a == address {
-                    Ok(())
-                } else {
-                    Err(Error::UnexpectedResponseData)
+    /// Changes the Modbus address of the motor
+    ///
+    /// 1-247.
+    /// Once saved, this [`Motor`] switches to the new address and checks that the motor responds
+    /// there.
+    /// If it does not, this [`Motor`] switches back to the original address and, if the motor
+    /// still responds there, the original address is written back.
+    pub async fn set_device_address(&mut self, address: u8) -> Result<()> {
+        if !(1..=247).contains(&address) {
+            return Err(Error::InvalidArgument);
+        }
+
+        let original = self.address;
+        if address == original {
+            return Ok(());
+        }
+
+        self.write_one_word_parameter(0x00, UNLOCK, Ok).await?;
+        self.write_one_word_parameter(0x15, address as u16, Ok)
+            .await?;
+
+        // The motor may already have switched to the new address, in which case the reply to
+        // the save comes from there (or not at all)
+        if let Err(e) = self.write_one_word_parameter(0x00, SAVE, Ok).await {
+            debug!("No reply to save at the original address: {}", e);
+            self.address = address;
+            let _ = self.write_one_word_parameter(0x00, SAVE, Ok).await;
+        }
+
+        self.address = address;
+
+        match self.device_address().await {
+            Ok(a) if a == address as u16 => {
+                info!("Motor address changed from {} to {}", original, address);
+                Ok(())
+            }
+            result => {
+                warn!(
+                    "Motor did not respond at new address {}: {:?}",
+                    address, result
+                );
+                self.address = original;
+
+                if self.device_address().await.is_ok() {
+                    self.write_one_word_parameter(0x00, UNLOCK, Ok).await?;
+                    self.write_one_word_parameter(0x15, original as u16, Ok)
+                        .await?;
+                    self.write_one_word_parameter(0x00, SAVE, Ok).await?;
                 }
+
+                Err(Error::ReconfigurationFailed)
             }
-            _ => Err(Error::UnexpectedResponseType),
         }
     }
+}
 
-    pub async fn set_baud_rate(&mut self, baud: RtuBaud) -> Result<()> {
-        let baud = match baud {
-            RtuBaud::Baud115200 => 803,
-            RtuBaud::Baud38400 => 802,
-            RtuBaud::Baud19200 => 801,
-            RtuBaud::Baud9600 => 800,
-        };
+impl<I: embedded_io_async::Read + embedded_io_async::Write + SetBaudRate> Motor<I> {
+    /// Connects to a motor whose baud rate is not known
+    ///
+    /// Each baud rate in [`RtuBaud::ALL`] is tried in turn, until the motor sends a valid reply.
+    pub async fn autodetect(comm: I, address: u8, response_timeout: Duration) -> Result<Self> {
+        let mut motor = Self::new(comm, RtuBaud::ALL[0], address, response_timeout);
+
+        for baud in RtuBaud::ALL {
+            motor.set_transport_config(baud.into())?;
+
+            // Noise can fail to decode like an exception does, so only a valid reply counts
+            match motor.device_address().await {
+                Ok(_) => {
+                    info!("Motor {} detected at {} baud", address, baud.rate());
+                    return Ok(motor);
+                }
+                Err(e) => debug!("No response at {} baud: {}", baud.rate(), e),
+            }
+        }
 
-        self.write_one_word_parameter(0x00, 1, Ok).await?;
-        self.write_one_word_parameter(0x02, baud, Ok).await?;
-        self.write_one_word_parameter(0x03, 129, Ok).await?;
-        self.write_one_word_parameter(0x00, 506, Ok).await?;
+        warn!("Motor {} not detected at any baud rate", address);
+        Err(Error::BaudNotDetected(address, RtuBaud::ALL))
+    }
+}
 
-        Ok(())
+impl<I: embedded_io_async::Read + embedded_io_async::Write + SetBaudRate, const N: usize>
+    Motor<I, N>
+{
+    /// Changes the baud rate of the motor and the transport, then checks the motor responds
+    ///
+    /// This is [`Motor::set_baud_rate`] followed by [`Motor::switch_baud`], the motor must be
+    /// power cycled within `reconnect_timeout` of the new baud rate being saved.
+    pub async fn reconfigure_baud(
+        &mut self,
+        baud: RtuBaud,
+        reconnect_timeout: Duration,
+    ) -> Result<()> {
+        self.set_baud_rate(baud).await?;
+        self.switch_baud(baud, reconnect_timeout).await
     }
 
-    pub async fn set_target_position_custom(&mut self, value: u32) -> Result<()> {
-        const FC: FunctionCode = FunctionCode::Custom(0x78);
+    /// Switches the transport to the baud rate saved by [`Motor::set_baud_rate`], then checks the
+    /// motor responds
+    ///
+    /// The motor only switches to the new baud rate once it is power cycled, which must happen
+    /// within `reconnect_timeout`.
+    /// If the motor does not respond in time the transport is switched back to the original baud
+    /// rate and [`Error::ReconfigurationFailed`] is returned, this can be retried without saving
+    /// the baud rate again.
+    pub async fn switch_baud(&mut self, baud: RtuBaud, reconnect_timeout: Duration) -> Result<()> {
+        let original = self.serial;
+
+        self.set_transport_config(SerialConfig {
+            baud: baud.rate(),
+            ..original
+        })?;
+
+        if self.wait_until_reachable(reconnect_timeout).await {
+            info!("Motor reachable at {} baud", baud.rate());
+            Ok(())
+        } else {
+            warn!("Motor not reachable at {} baud", baud.rate());
+            self.set_transport_config(original)?;
+            Err(Error::ReconfigurationFailed)
+        }
+    }
 
-        let data = value.to_be_bytes();
-        let request = RequestPdu(Request::Custom(FC, &data));
+    /// Changes the serial configuration of the transport, and the frame timing to match
+    fn set_transport_config(&mut self, serial: SerialConfig) -> Result<()> {
+        self.comm.set_serial_config(&serial)?;
+        self.cache.invalidate();
 
-        match self.modbus_transaction(request).await? {
-            Response::Custom(FC, d) => {
-                if d == data {
-                    Ok(())
-                } else {
-                    Err(Error::UnexpectedResponseData)
-                }
-            }
-            _ => Err(Error::UnexpectedResponseType),
-        }
+        self.serial = serial;
+        self.t15 = serial.t15();
+        self.t35 = serial.t35();
+        self.earliest_next_frame = Instant::now() + self.t35;
+
+        Ok(())
     }
+}
 
-    pub async fn set_absolute_position_custom(&mut self, value: u32) -> Result<()> {
-        const FC: FunctionCode = FunctionCode::Custom(0x7B);
+// modbus-core cannot determine the length of a frame with a custom function code, so treat
+// everything that was received as a single frame.
+fn decode_custom_response(data: &[u8]) -> Result<ResponsePdu<'_>> {
+    if data.len() < 4 {
+        return Err(Error::Modbus);
+    }
 
-        let data = value.to_be_bytes();
-        let request = RequestPdu(Request::Custom(FC, &data));
+    let frame = modbus_core::rtu::extract_frame(data, data.len() - 3)
+        .map_err(|_| Error::Modbus)?
+        .ok_or(Error::Modbus)?;
 
-        match self.modbus_transaction(request).await? {
-            Response::Custom(FC, d) => {
-                if d == data {
-                    Ok(())
-                } else {
-                    Err(Error::UnexpectedResponseData)
-                }
-            }
-            _ => Err(Error::UnexpectedResponseType),
-        }
+    decode_pdu(frame.pdu)
+}
+
+fn decode_pdu(pdu: &[u8]) -> Result<ResponsePdu<'_>> {
+    match ExceptionResponse::try_from(pdu) {
+        Ok(exception) => Ok(ResponsePdu(Err(exception))),
+        Err(_) => Response::try_from(pdu)
+            .map(|response| ResponsePdu(Ok(response)))
+            .map_err(|_| Error::Modbus),
     }
 }
@@ -1,8 +1,8 @@
 use super::Motor;
-use crate::{AlarmCode, Direction, Error, Result};
+use crate::{AlarmCode, Direction, Error, MotorStatus, Result};
 use embassy_time::Duration;
 
-impl<I: embedded_io_async::Read + embedded_io_async::Write> Motor<I> {
+impl<I: embedded_io_async::Read + embedded_io_async::Write, const N: usize> Motor<I, N> {
     pub async fn modbus_enabled(&mut self) -> Result<bool> {
         self.read_one_word_parameter(0x00, |v| match v {
             0 => Ok(false),
@@ -84,7 +84,7 @@ impl<I: embedded_io_async::Read + embedded_io_async::Write> Motor<I> {
     }
 
     pub async fn set_speed_i_time(&mut self, value: Duration) -> Result<()> {
-        self.write_one_word_parameter(0x06, value, |v| Ok(v.as_millis() as u16))
+        self.write_one_word_parameter(0x06, value, |v| Ok(speed_i_time_to_raw(v)))
             .await
     }
 
@@ -102,7 +102,7 @@ impl<I: embedded_io_async::Read + embedded_io_async::Write> Motor<I> {
     }
 
     pub async fn set_speed_feed(&mut self, value: f32) -> Result<()> {
-        self.write_one_word_parameter(0x08, value, |v| Ok((v * 327.) as u16))
+        self.write_one_word_parameter(0x08, value, |v| Ok(speed_feed_to_raw(v)))
             .await
     }
 
@@ -116,11 +116,8 @@ impl<I: embedded_io_async::Read + embedded_io_async::Write> Motor<I> {
     }
 
     pub async fn set_dir_polarity(&mut self, value: Direction) -> Result<()> {
-        self.write_one_word_parameter(0x09, value, |v| match v {
-            Direction::CounterClockwise => Ok(0),
-            Direction::Clockwise => Ok(1),
-        })
-        .await
+        self.write_one_word_parameter(0x09, value, |v| Ok(dir_polarity_to_raw(v)))
+            .await
     }
 
     pub async fn electronic_gear_numerator(&mut self) -> Result<u16> {
@@ -147,48 +144,46 @@ impl<I: embedded_io_async::Read + embedded_io_async::Write> Motor<I> {
     }
 
     pub async fn target_position(&mut self) -> Result<u32> {
-        self.read_two_word_parameter(0x0C, |msb, lsb| {
-            let msb = msb.to_be_bytes();
-            let lsb = lsb.to_be_bytes();
-            Ok(u32::from_be_bytes([lsb[0], lsb[1], msb[0], msb[1]]))
-        })
-        .await
+        self.read_two_word_parameter(0x0C, |lsb, msb| Ok(position_from_raw(lsb, msb)))
+            .await
     }
 
+    /// Sets the target position
+    ///
+    /// The position is checked against the soft limits, see [`Motor::set_soft_limits`].
     pub async fn set_target_position(&mut self, value: u32) -> Result<()> {
-        self.write_two_word_parameter(0x0C, value, |v| {
-            let data = v.to_be_bytes();
-            let msb = u16::from_be_bytes([data[0], data[1]]);
-            let lsb = u16::from_be_bytes([data[2], data[3]]);
-            Ok([lsb, msb])
-        })
-        .await
+        let value = self.limit_position(value)?;
+
+        self.write_two_word_parameter(0x0C, value, |v| Ok(position_to_raw(v)))
+            .await
     }
 
+    /// Gets the active alarm, if there is one
+    ///
+    /// With the `alarm-history` feature, a change in alarm is recorded, see `Motor::alarm_history`.
     pub async fn alarm_code(&mut self) -> Result<Option<AlarmCode>> {
-        self.read_one_word_parameter(0x0E, |v| match v {
-            0 => Ok(None),
-            0x10 => Ok(Some(AlarmCode::PowerFailure)),
-            0x12 => Ok(Some(AlarmCode::Overflow)),
-            0x14 => Ok(Some(AlarmCode::Block)),
-            0x15 => Ok(Some(AlarmCode::Overpressure)),
-            _ => Err(Error::UnexpectedResponseData),
-        })
-        .await
+        let alarm = self
+            .read_one_word_parameter(0x0E, |v| Ok(AlarmCode::from_register(v)))
+            .await?;
+
+        #[cfg(feature = "alarm-history")]
+        self.alarms.observe(alarm);
+
+        Ok(alarm)
     }
 
     pub async fn current(&mut self) -> Result<f32> {
-        self.read_one_word_parameter(0x0F, |v| Ok(v as f32 / 2000.))
+        self.read_one_word_parameter(0x0F, |v| Ok(current_from_raw(v)))
             .await
     }
 
     pub async fn speed(&mut self) -> Result<f32> {
-        self.read_one_word_parameter(0x10, |v| Ok(v as f32 / 10.))
+        self.read_one_word_parameter(0x10, |v| Ok(speed_from_raw(v)))
             .await
     }
 
     pub async fn voltage(&mut self) -> Result<f32> {
-        self.read_one_word_parameter(0x11, |v| Ok(v as f32 / 327.))
+        self.read_one_word_parameter(0x11, |v| Ok(voltage_from_raw(v)))
             .await
     }
 
@@ -200,6 +195,43 @@ impl<I: embedded_io_async::Read + embedded_io_async::Write> Motor<I> {
         self.read_one_word_parameter(0x13, Ok).await
     }
 
+    /// Gets all of the live status registers (0x0E-0x13) in a single transaction
+    ///
+    /// With the `alarm-history` feature, a change in alarm is recorded, see `Motor::alarm_history`.
+    pub async fn status(&mut self) -> Result<MotorStatus> {
+        let mut raw = [0u16; 6];
+        self.read_parameters(0x0E, &mut raw).await?;
+
+        Ok(self.status_from_raw(&raw))
+    }
+
+    /// Gets the live status registers and the absolute position (0x0E-0x17) in a single
+    /// transaction
+    ///
+    /// With the `alarm-history` feature, a change in alarm is recorded, see `Motor::alarm_history`.
+    pub async fn status_and_position(&mut self) -> Result<(MotorStatus, u32)> {
+        let mut raw = [0u16; 10];
+        self.read_parameters(0x0E, &mut raw).await?;
+
+        let status = self.status_from_raw(&raw[..6]);
+        Ok((status, position_from_raw(raw[8], raw[9])))
+    }
+
+    fn status_from_raw(&mut self, raw: &[u16]) -> MotorStatus {
+        let alarm = AlarmCode::from_register(raw[0]);
+        #[cfg(feature = "alarm-history")]
+        self.alarms.observe(alarm);
+
+        MotorStatus {
+            alarm,
+            current: current_from_raw(raw[1]),
+            speed: speed_from_raw(raw[2]),
+            voltage: voltage_from_raw(raw[3]),
+            temperature: raw[4],
+            pwm: raw[5],
+        }
+    }
+
     pub async fn parameter_save_flag(&mut self) -> Result<bool> {
         self.read_one_word_parameter(0x14, |v| match v {
             0 => Ok(false),
@@ -222,22 +254,18 @@ impl<I: embedded_io_async::Read + embedded_io_async::Write> Motor<I> {
     }
 
     pub async fn absolute_position(&mut self) -> Result<u32> {
-        self.read_two_word_parameter(0x16, |msb, lsb| {
-            let msb = msb.to_be_bytes();
-            let lsb = lsb.to_be_bytes();
-            Ok(u32::from_be_bytes([lsb[0], lsb[1], msb[0], msb[1]]))
-        })
-        .await
+        self.read_two_word_parameter(0x16, |lsb, msb| Ok(position_from_raw(lsb, msb)))
+            .await
     }
 
+    /// Sets the absolute position
+    ///
+    /// The position is checked against the soft limits, see [`Motor::set_soft_limits`].
     pub async fn set_absolute_position(&mut self, value: u32) -> Result<()> {
-        self.write_two_word_parameter(0x16, value, |v| {
-            let data = v.to_be_bytes();
-            let msb = u16::from_be_bytes([data[0], data[1]]);
-            let lsb = u16::from_be_bytes([data[2], data[3]]);
-            Ok([lsb, msb])
-        })
-        .await
+        let value = self.limit_position(value)?;
+
+        self.write_two_word_parameter(0x16, value, |v| Ok(position_to_raw(v)))
+            .await
     }
 
     pub async fn still_maximum_allowed_current(&mut self) -> Result<u16> {
@@ -256,3 +284,41 @@ impl<I: embedded_io_async::Read + embedded_io_async::Write> Motor<I> {
         self.write_one_word_parameter(0x19, value, Ok).await
     }
 }
+
+pub(super) fn position_to_raw(value: u32) -> [u16; 2] {
+    let data = value.to_be_bytes();
+    let msb = u16::from_be_bytes([data[0], data[1]]);
+    let lsb = u16::from_be_bytes([data[2], data[3]]);
+    [lsb, msb]
+}
+
+pub(super) fn speed_i_time_to_raw(value: Duration) -> u16 {
+    value.as_millis() as u16
+}
+
+pub(super) fn speed_feed_to_raw(value: f32) -> u16 {
+    (value * 327.) as u16
+}
+
+pub(super) fn dir_polarity_to_raw(value: Direction) -> u16 {
+    match value {
+        Direction::CounterClockwise => 0,
+        Direction::Clockwise => 1,
+    }
+}
+
+pub(super) fn position_from_raw(lsb: u16, msb: u16) -> u32 {
+    (msb as u32) << 16 | lsb as u32
+}
+
+fn current_from_raw(value: u16) -> f32 {
+    value as f32 / 2000.
+}
+
+fn speed_from_raw(value: u16) -> f32 {
+    value as f32 / 10.
+}
+
+fn voltage_from_raw(value: u16) -> f32 {
+    value as f32 / 327.
+}
@@ -0,0 +1,122 @@
+use super::Motor;
+use core::ops::Range;
+use embassy_time::{Duration, Instant};
+
+/// How requests and responses are framed on the wire.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Protocol {
+    /// Modbus RTU on a serial line
+    #[default]
+    Rtu,
+
+    /// Modbus RTU frames passed through a TCP connection, e.g. by a transparent serial to
+    /// Ethernet gateway
+    RtuOverTcp,
+
+    /// Modbus TCP, each frame has an MBAP header instead of a CRC
+    Tcp,
+}
+
+impl<I: embedded_io_async::Read + embedded_io_async::Write> Motor<I> {
+    /// Creates a motor reached through a Modbus TCP gateway, using `address` as the unit ID
+    pub fn new_tcp(comm: I, address: u8, response_timeout: Duration) -> Self {
+        Self::new_network(comm, Protocol::Tcp, address, response_timeout)
+    }
+
+    /// Creates a motor reached through a gateway that passes RTU frames over TCP unchanged
+    pub fn new_rtu_over_tcp(comm: I, address: u8, response_timeout: Duration) -> Self {
+        Self::new_network(comm, Protocol::RtuOverTcp, address, response_timeout)
+    }
+
+    fn new_network(comm: I, protocol: Protocol, address: u8, response_timeout: Duration) -> Self {
+        let mut motor = Self::new(comm, crate::RtuBaud::Baud115200, address, response_timeout);
+
+        // The gateway takes care of the timing on the serial line
+        motor.protocol = protocol;
+        motor.t15 = Duration::from_ticks(0);
+        motor.t35 = Duration::from_ticks(0);
+        motor.earliest_next_frame = Instant::from_ticks(0);
+        motor
+    }
+}
+
+impl<I: embedded_io_async::Read + embedded_io_async::Write, const N: usize> Motor<I, N> {
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+}
+
+// Length of the MBAP header, including the unit ID
+pub(super) const MBAP_LEN: usize = 7;
+
+/// Finds a complete Modbus TCP response frame with the transaction and unit ID
+///
+/// Complete frames for other transactions (e.g. late responses to earlier requests), and for
+/// protocols other than Modbus, are skipped.
+pub(super) fn find_tcp_frame(
+    data: &[u8],
+    transaction_id: u16,
+    unit_id: u8,
+) -> Option<Range<usize>> {
+    let mut start = 0;
+
+    loop {
+        let frame = data.get(start..start + MBAP_LEN)?;
+        let len = 6 + u16::from_be_bytes([frame[4], frame[5]]) as usize;
+        if data.len() < start + len {
+            return None;
+        }
+
+        let id = u16::from_be_bytes([frame[0], frame[1]]);
+        let protocol_id = u16::from_be_bytes([frame[2], frame[3]]);
+        if id == transaction_id && protocol_id == 0 && frame[6] == unit_id && len > MBAP_LEN {
+            return Some(start..start + len);
+        }
+
+        start += len;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    fn frame(transaction_id: u16, protocol_id: u16, pdu: &[u8]) -> Vec<u8> {
+        let mut frame = Vec::new();
+        frame.extend_from_slice(&transaction_id.to_be_bytes());
+        frame.extend_from_slice(&protocol_id.to_be_bytes());
+        frame.extend_from_slice(&(pdu.len() as u16 + 1).to_be_bytes());
+        frame.push(1);
+        frame.extend_from_slice(pdu);
+        frame
+    }
+
+    #[test]
+    fn finds_frame() {
+        let response = frame(7, 0, &[0x03, 0x02, 0x00, 0x1F]);
+        assert_eq!(find_tcp_frame(&response, 7, 1), Some(0..response.len()));
+        assert_eq!(find_tcp_frame(&response, 7, 2), None);
+    }
+
+    #[test]
+    fn skips_late_frame_and_other_protocols() {
+        let late = frame(6, 0, &[0x03, 0x02, 0x00, 0x63]);
+        let other = frame(7, 1, &[0x83, 0x02]);
+        let response = frame(7, 0, &[0x03, 0x02, 0x00, 0x1F]);
+        let data = [late.as_slice(), &other, &response].concat();
+
+        assert_eq!(
+            find_tcp_frame(&data, 7, 1),
+            Some(late.len() + other.len()..data.len())
+        );
+    }
+
+    #[test]
+    fn incomplete_frame_is_not_found() {
+        let response = frame(7, 0, &[0x03, 0x02, 0x00, 0x1F]);
+        assert_eq!(find_tcp_frame(&response[..response.len() - 1], 7, 1), None);
+        assert_eq!(find_tcp_frame(&response[..3], 7, 1), None);
+    }
+}
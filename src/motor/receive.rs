@@ -0,0 +1,235 @@
+use super::Motor;
+use core::ops::Range;
+use embassy_time::{Duration, Instant};
+use modbus_core::rtu::{extract_frame, response_pdu_len};
+
+/// How the end of a response from the motor is detected.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReceiveMode {
+    /// The response is complete as soon as a frame of the expected length with a valid CRC has
+    /// been received
+    ///
+    /// Falls back to [`ReceiveMode::Gap`] for responses whose length cannot be known in advance.
+    #[default]
+    FrameLength,
+
+    /// The response is complete once nothing has been received for T1.5
+    Gap,
+}
+
+impl<I: embedded_io_async::Read + embedded_io_async::Write, const N: usize> Motor<I, N> {
+    pub fn set_receive_mode(&mut self, mode: ReceiveMode) {
+        self.receive.mode = mode;
+    }
+
+    pub fn receive_mode(&self) -> ReceiveMode {
+        self.receive.mode
+    }
+
+    /// Sets if the transport receives everything it sends (e.g. some RS-485 adapters), in which
+    /// case the echo of each request is discarded
+    pub fn set_local_echo(&mut self, echo: bool) {
+        self.receive.local_echo = echo;
+    }
+
+    /// Gets the time taken by the last transaction that received a response, from starting to
+    /// send the request to receiving the end of the response
+    pub fn last_transaction_time(&self) -> Option<Duration> {
+        self.receive.last_transaction_time
+    }
+}
+
+#[derive(Debug, Default)]
+pub(super) struct ReceiveState {
+    pub(super) mode: ReceiveMode,
+    pub(super) local_echo: bool,
+    pub(super) last_transaction_time: Option<Duration>,
+
+    // When the first byte of the current response was received
+    pub(super) first_byte: Option<Instant>,
+}
+
+/// Finds a complete response frame from a slave to a request with the function code
+///
+/// Bytes before the frame (noise, or the end of an earlier frame) are skipped.
+pub(super) fn find_frame(data: &[u8], slave: u8, function: u8) -> Option<Range<usize>> {
+    (0..data.len()).find_map(|start| {
+        let candidate = &data[start..];
+        if candidate.len() < 2 || candidate[0] != slave || candidate[1] & 0x7F != function {
+            return None;
+        }
+
+        let pdu_len = if candidate[1] & 0x80 != 0 {
+            // Exception responses have the same length for every function code
+            2
+        } else {
+            response_pdu_len(candidate).ok()??
+        };
+
+        extract_frame(candidate, pdu_len)
+            .ok()?
+            .map(|_| start..start + pdu_len + 3)
+    })
+}
+
+/// Finds a response frame that ends with the received data, for responses whose length is not
+/// known in advance
+pub(super) fn find_unsized_frame(data: &[u8], slave: u8, function: u8) -> Option<Range<usize>> {
+    (0..data.len()).find_map(|start| {
+        let candidate = &data[start..];
+        if candidate.len() < 5 || candidate[0] != slave || candidate[1] != function {
+            return None;
+        }
+
+        extract_frame(candidate, candidate.len() - 3)
+            .ok()?
+            .map(|_| start..data.len())
+    })
+}
+
+/// If the length of responses with the function code is known before they are received
+pub(super) fn has_known_length(function: u8) -> bool {
+    response_pdu_len(&[0, function]).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::testing::{ADDRESS, TestBus, motor},
+        *,
+    };
+    use crate::Error;
+    use embassy_futures::block_on;
+    use modbus_core::rtu::crc16;
+    use std::vec::Vec;
+
+    fn frame(pdu: &[u8]) -> Vec<u8> {
+        let mut frame = std::vec![ADDRESS];
+        frame.extend_from_slice(pdu);
+        let crc = crc16(&frame);
+        frame.extend_from_slice(&crc.to_be_bytes());
+        frame
+    }
+
+    #[test]
+    fn finds_frame_after_garbage() {
+        let response = frame(&[0x03, 0x02, 0x12, 0x34]);
+        let mut data = std::vec![0xFF, ADDRESS, 0x03, 0x00];
+        data.extend_from_slice(&response);
+
+        assert_eq!(
+            find_frame(&data, ADDRESS, 0x03),
+            Some(4..4 + response.len())
+        );
+    }
+
+    #[test]
+    fn skips_late_frame_for_another_function() {
+        let late = frame(&[0x06, 0x00, 0x02, 0x00, 0x64]);
+        let response = frame(&[0x03, 0x02, 0x12, 0x34]);
+        let data = [late.as_slice(), &response].concat();
+
+        assert_eq!(
+            find_frame(&data, ADDRESS, 0x03),
+            Some(late.len()..data.len())
+        );
+    }
+
+    #[test]
+    fn finds_exception() {
+        let exception = frame(&[0x83, 0x02]);
+        assert_eq!(
+            find_frame(&exception, ADDRESS, 0x03),
+            Some(0..exception.len())
+        );
+    }
+
+    #[test]
+    fn incomplete_or_corrupt_frame_is_not_found() {
+        let response = frame(&[0x03, 0x02, 0x12, 0x34]);
+        assert_eq!(
+            find_frame(&response[..response.len() - 1], ADDRESS, 0x03),
+            None
+        );
+
+        let mut corrupt = response.clone();
+        corrupt[3] ^= 1;
+        assert_eq!(find_frame(&corrupt, ADDRESS, 0x03), None);
+
+        assert_eq!(find_frame(&response, ADDRESS + 1, 0x03), None);
+    }
+
+    #[test]
+    fn finds_unsized_frame_ending_with_data() {
+        let response = frame(&[0x78, 0x00, 0x01, 0x02, 0x03]);
+        let data = [&[0x00, 0xFF][..], &response].concat();
+
+        assert_eq!(
+            find_unsized_frame(&data, ADDRESS, 0x78),
+            Some(2..data.len())
+        );
+        assert!(!has_known_length(0x78));
+        assert!(has_known_length(0x03));
+    }
+
+    fn read_with(mode: ReceiveMode, configure: impl FnOnce(&mut TestBus)) -> Result<u16, Error> {
+        let mut bus = TestBus::new();
+        bus.registers[0x12] = 31;
+        configure(&mut bus);
+
+        let mut motor = motor(bus);
+        motor.set_receive_mode(mode);
+        motor.set_local_echo(motor.transport().echo);
+
+        block_on(motor.temperature())
+    }
+
+    #[test]
+    fn local_echo_is_skipped() {
+        for mode in [ReceiveMode::FrameLength, ReceiveMode::Gap] {
+            assert_eq!(read_with(mode, |bus| bus.echo = true), Ok(31), "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn response_after_echo_is_waited_for() {
+        for mode in [ReceiveMode::FrameLength, ReceiveMode::Gap] {
+            let result = read_with(mode, |bus| {
+                bus.echo = true;
+                bus.delay = Duration::from_millis(10);
+            });
+            assert_eq!(result, Ok(31), "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn response_in_pieces_after_garbage() {
+        for mode in [ReceiveMode::FrameLength, ReceiveMode::Gap] {
+            let result = read_with(mode, |bus| {
+                bus.prefix = std::vec![0x00, 0xFF];
+                bus.chunk = 3;
+            });
+            assert_eq!(result, Ok(31), "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn late_bytes_are_discarded() {
+        for mode in [ReceiveMode::FrameLength, ReceiveMode::Gap] {
+            let result = read_with(mode, |bus| bus.receive(&frame(&[0x03, 0x02, 0x00, 0x63])));
+            assert_eq!(result, Ok(31), "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn no_response_times_out() {
+        for mode in [ReceiveMode::FrameLength, ReceiveMode::Gap] {
+            let result = read_with(mode, |bus| {
+                bus.echo = true;
+                bus.silent = 1;
+            });
+            assert_eq!(result, Err(Error::Timeout), "{mode:?}");
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use super::Motor;
+use crate::{Result, RtuBaud, SerialConfig, SetBaudRate};
+use core::ops::ControlFlow;
+use embassy_time::Duration;
+
+/// A motor that responded during a [`scan`].
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredMotor {
+    pub baud: RtuBaud,
+    pub address: u8,
+
+    /// Raw values of the parameter registers 0x00-0x0B, to help identify the motor
+    ///
+    /// `None` if the motor responded to the probe but the parameters could not be read.
+    pub fingerprint: Option<[u16; 12]>,
+}
+
+/// Progress of a [`scan`], reported after each address is probed.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone)]
+pub struct ScanProgress {
+    pub baud: RtuBaud,
+    pub address: u8,
+
+    /// Number of addresses probed so far, across all baud rates
+    pub probed: usize,
+
+    /// Number of addresses that will be probed in total
+    pub total: usize,
+
+    /// The motor found at this address, if there was one
+    pub found: Option<DiscoveredMotor>,
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Default)]
+pub struct ScanSummary {
+    /// Number of addresses probed
+    pub probed: usize,
+
+    /// Number of motors found
+    pub found: usize,
+
+    /// If the scan was stopped before every address was probed
+    pub cancelled: bool,
+}
+
+/// Searches for motors on a bus at each combination of baud rate and address
+///
+/// Each address is probed by reading the device address register with a short timeout, so this
+/// takes roughly `bauds.len() * addresses.len() * response_timeout` when the bus is empty.
+/// The progress callback is called after every probe, returning [`ControlFlow::Break`] from it
+/// cancels the scan.
+/// Motors are reported through the progress callback as they are found.
+/// `current` is the serial configuration the transport is in, which it is returned to before
+/// this returns, whether the scan completes, is cancelled through the callback or fails, but not
+/// if this future is dropped.
+pub async fn scan<I, A, F>(
+    comm: I,
+    current: impl Into<SerialConfig>,
+    addresses: A,
+    bauds: &[RtuBaud],
+    response_timeout: Duration,
+    mut progress: F,
+) -> Result<ScanSummary>
+where
+    I: embedded_io_async::Read + embedded_io_async::Write + SetBaudRate,
+    A: IntoIterator<Item = u8> + Clone,
+    F: FnMut(&ScanProgress) -> ControlFlow<()>,
+{
+    let current = current.into();
+    let mut motor = Motor::new(comm, current, 1, response_timeout);
+
+    let result = probe_all(&mut motor, addresses, bauds, &mut progress).await;
+
+    // Even if changing it failed, the transport may have been left part way through the change
+    if !bauds.is_empty() {
+        debug!("Restoring the serial configuration after scanning");
+        if let Err(e) = motor.set_transport_config(current) {
+            warn!("Failed to restore the serial configuration: {}", e);
+            return result.and(Err(e));
+        }
+    }
+
+    result
+}
+
+async fn probe_all<I, A, F, const N: usize>(
+    motor: &mut Motor<I, N>,
+    addresses: A,
+    bauds: &[RtuBaud],
+    progress: &mut F,
+) -> Result<ScanSummary>
+where
+    I: embedded_io_async::Read + embedded_io_async::Write + SetBaudRate,
+    A: IntoIterator<Item = u8> + Clone,
+    F: FnMut(&ScanProgress) -> ControlFlow<()>,
+{
+    let total = bauds.len() * addresses.clone().into_iter().count();
+    let mut summary = ScanSummary::default();
+
+    for baud in bauds {
+        info!("Scanning at {} baud", baud.rate());
+        motor.set_transport_config((*baud).into())?;
+
+        for address in addresses.clone() {
+            motor.address = address;
+
+            let found = match motor.device_address().await {
+                Ok(_) => {
+                    let mut fingerprint = [0u16; 12];
+                    let fingerprint = motor
+                        .read_parameters(0x00, &mut fingerprint)
+                        .await
+                        .ok()
+                        .map(|_| fingerprint);
+
+                    let found = DiscoveredMotor {
+                        baud: *baud,
+                        address,
+                        fingerprint,
+                    };
+                    info!("Found motor: {:?}", found);
+
+                    summary.found += 1;
+                    Some(found)
+                }
+                Err(e) => {
+                    // At the wrong baud rate replies from other motors may show up as any error
+                    debug!("No response at address {}: {}", address, e);
+                    None
+                }
+            };
+
+            summary.probed += 1;
+
+            let p = ScanProgress {
+                baud: *baud,
+                address,
+                probed: summary.probed,
+                total,
+                found,
+            };
+
+            if progress(&p).is_break() {
+                info!("Scan cancelled");
+                summary.cancelled = true;
+                return Ok(summary);
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::testing::TestBus, *};
+    use crate::Error;
+    use embassy_futures::block_on;
+    use std::vec::Vec;
+
+    const BAUDS: [RtuBaud; 3] = [RtuBaud::Baud9600, RtuBaud::Baud38400, RtuBaud::Baud115200];
+
+    fn scan_with(
+        bus: &mut TestBus,
+        mut progress: impl FnMut(&ScanProgress) -> ControlFlow<()>,
+    ) -> Result<Vec<DiscoveredMotor>> {
+        let mut found = Vec::new();
+        block_on(scan(
+            &mut *bus,
+            RtuBaud::Baud19200,
+            1..=2,
+            &BAUDS,
+            Duration::from_millis(10),
+            |p: &ScanProgress| {
+                found.extend(p.found.clone());
+                progress(p)
+            },
+        ))?;
+        Ok(found)
+    }
+
+    #[test]
+    fn finds_motor_and_restores_baud_rate() {
+        let mut bus = TestBus::new();
+        bus.motor_baud = Some(38400);
+
+        let found = scan_with(&mut bus, |_| ControlFlow::Continue(())).unwrap();
+
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].baud, found[0].address), (RtuBaud::Baud38400, 1));
+        assert_eq!(bus.serial, Some(SerialConfig::new(19200)));
+    }
+
+    #[test]
+    fn cancelled_scan_restores_baud_rate() {
+        let mut bus = TestBus::new();
+
+        let found = scan_with(&mut bus, |_| ControlFlow::Break(())).unwrap();
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(bus.serial, Some(SerialConfig::new(19200)));
+    }
+
+    #[test]
+    fn failed_scan_restores_baud_rate() {
+        let mut bus = TestBus::new();
+        bus.unsupported_baud = Some(115200);
+
+        assert_eq!(
+            scan_with(&mut bus, |_| ControlFlow::Continue(())).err(),
+            Some(Error::Transport)
+        );
+        assert_eq!(bus.serial, Some(SerialConfig::new(19200)));
+    }
+}
@@ -0,0 +1,204 @@
+//! A simulated motor on a serial line, for testing requests and responses on the host.
+
+use super::Motor;
+use crate::{Error, Result, RtuBaud, SerialConfig, SetBaudRate};
+use embassy_time::{Duration, Instant, Timer};
+use modbus_core::rtu::crc16;
+use std::{collections::VecDeque, vec, vec::Vec};
+
+pub(super) const ADDRESS: u8 = 1;
+
+/// Creates a motor on a simulated bus at 115200 baud
+pub(super) fn motor(bus: TestBus) -> Motor<TestBus> {
+    Motor::new(bus, RtuBaud::Baud115200, ADDRESS, Duration::from_millis(50))
+}
+
+/// A transport connected to a single simulated motor.
+pub(super) struct TestBus {
+    pub(super) registers: [u16; 0x20],
+
+    /// Every request is received back before its response
+    pub(super) echo: bool,
+
+    /// Sent before every response, e.g. noise or the end of an earlier frame
+    pub(super) prefix: Vec<u8>,
+
+    /// Time from a request being sent to its response arriving
+    pub(super) delay: Duration,
+
+    /// Writes of multiple registers are rejected with an exception
+    pub(super) reject_multiple: bool,
+
+    /// Number of upcoming requests that are not answered
+    pub(super) silent: usize,
+
+    /// Maximum number of bytes returned by each read
+    pub(super) chunk: usize,
+
+    /// Every request sent
+    pub(super) requests: Vec<Vec<u8>>,
+
+    /// Serial configuration the transport was last changed to
+    pub(super) serial: Option<SerialConfig>,
+
+    /// A baud rate the transport cannot be changed to
+    pub(super) unsupported_baud: Option<u32>,
+
+    /// Baud rate the motor responds at, if it must match the transport
+    pub(super) motor_baud: Option<u32>,
+
+    rx: VecDeque<u8>,
+    pending: VecDeque<(Instant, Vec<u8>)>,
+}
+
+impl TestBus {
+    pub(super) fn new() -> Self {
+        let mut registers = [0; 0x20];
+        registers[0x15] = ADDRESS as u16;
+
+        Self {
+            registers,
+            echo: false,
+            prefix: Vec::new(),
+            delay: Duration::from_ticks(0),
+            reject_multiple: false,
+            silent: 0,
+            chunk: usize::MAX,
+            requests: Vec::new(),
+            serial: None,
+            unsupported_baud: None,
+            motor_baud: None,
+            rx: VecDeque::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Queues bytes to be received, as if they arrived late for an earlier request
+    pub(super) fn receive(&mut self, data: &[u8]) {
+        self.rx.extend(data);
+    }
+
+    fn respond(&mut self, request: &[u8]) {
+        self.requests.push(request.to_vec());
+
+        if self.echo {
+            self.rx.extend(request);
+        }
+
+        let n = request.len();
+        if n < 4 || crc16(&request[..n - 2]).to_be_bytes() != request[n - 2..] {
+            return;
+        }
+        if self.silent > 0 {
+            self.silent -= 1;
+            return;
+        }
+        if self
+            .motor_baud
+            .is_some_and(|baud| self.serial.is_some_and(|s| s.baud != baud))
+        {
+            return;
+        }
+        if request[0] != ADDRESS && request[0] != 0 {
+            return;
+        }
+
+        let word = |i: usize| u16::from_be_bytes([request[i], request[i + 1]]);
+        let start = word(2) as usize;
+
+        let mut pdu = match request[1] {
+            0x03 => {
+                let count = word(4) as usize;
+                match self.registers.get(start..start + count) {
+                    Some(values) => {
+                        let mut pdu = vec![0x03, (count * 2) as u8];
+                        for value in values {
+                            pdu.extend_from_slice(&value.to_be_bytes());
+                        }
+                        pdu
+                    }
+                    None => vec![0x83, 0x02],
+                }
+            }
+            0x06 => {
+                self.registers[start] = word(4);
+                request[1..6].to_vec()
+            }
+            0x10 if self.reject_multiple => vec![0x90, 0x01],
+            0x10 => {
+                let count = word(4) as usize;
+                for i in 0..count {
+                    self.registers[start + i] = word(7 + i * 2);
+                }
+                request[1..6].to_vec()
+            }
+            function => vec![function | 0x80, 0x01],
+        };
+
+        // Broadcasts are not answered
+        if request[0] == 0 {
+            return;
+        }
+
+        let mut response = self.prefix.clone();
+        let frame_start = response.len();
+        response.push(ADDRESS);
+        response.append(&mut pdu);
+        let crc = crc16(&response[frame_start..]);
+        response.extend_from_slice(&crc.to_be_bytes());
+
+        self.pending
+            .push_back((Instant::now() + self.delay, response));
+    }
+}
+
+impl SetBaudRate for TestBus {
+    fn set_baud_rate(&mut self, baud: u32) -> Result<()> {
+        self.set_serial_config(&SerialConfig::new(baud))
+    }
+
+    fn set_serial_config(&mut self, config: &SerialConfig) -> Result<()> {
+        if self.unsupported_baud == Some(config.baud) {
+            return Err(Error::Transport);
+        }
+        self.serial = Some(*config);
+        Ok(())
+    }
+}
+
+impl embedded_io_async::ErrorType for TestBus {
+    type Error = core::convert::Infallible;
+}
+
+impl embedded_io_async::Read for TestBus {
+    async fn read(&mut self, buf: &mut [u8]) -> core::result::Result<usize, Self::Error> {
+        loop {
+            if !self.rx.is_empty() {
+                let n = buf.len().min(self.rx.len()).min(self.chunk);
+                for (b, r) in buf.iter_mut().zip(self.rx.drain(..n)) {
+                    *b = r;
+                }
+                return Ok(n);
+            }
+
+            match self.pending.pop_front() {
+                Some((at, response)) => {
+                    Timer::at(at).await;
+                    self.rx.extend(response);
+                }
+                None => core::future::pending().await,
+            }
+        }
+    }
+}
+
+impl embedded_io_async::Write for TestBus {
+    async fn write(&mut self, buf: &[u8]) -> core::result::Result<usize, Self::Error> {
+        self.respond(buf);
+        Ok(buf.len())
+    }
+
+    async fn flush(&mut self) -> core::result::Result<(), Self::Error> {
+        Ok(())
+    }
+}
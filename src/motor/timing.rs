@@ -0,0 +1,200 @@
+use super::Motor;
+use embassy_time::Duration;
+
+// Number of function codes that timing is kept for
+const FUNCTIONS: usize = 6;
+
+/// Upper bounds of the buckets of [`FunctionTiming::histogram`], the last bucket holds everything
+/// slower.
+pub const HISTOGRAM_BOUNDS: [Duration; 8] = [
+    Duration::from_micros(250),
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_millis(2),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(20),
+    Duration::from_millis(50),
+];
+
+/// Time spent in each phase of a transaction.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransactionTiming {
+    /// Waiting for the inter-frame delay after the previous transaction
+    pub wait: Duration,
+
+    /// Handing the request to the transport
+    pub transmit: Duration,
+
+    /// From the request being sent to the first byte of the response
+    pub first_byte: Duration,
+
+    /// From the first byte of the response to the response being complete
+    pub completion: Duration,
+}
+
+impl TransactionTiming {
+    pub fn total(&self) -> Duration {
+        self.wait + self.transmit + self.first_byte + self.completion
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseStats {
+    pub count: u32,
+    pub min: Duration,
+    pub max: Duration,
+    pub total: Duration,
+}
+
+impl PhaseStats {
+    pub fn mean(&self) -> Option<Duration> {
+        (self.count > 0).then(|| self.total / self.count)
+    }
+
+    fn record(&mut self, duration: Duration) {
+        if self.count == 0 || duration < self.min {
+            self.min = duration;
+        }
+        self.max = self.max.max(duration);
+        self.total += duration;
+        self.count += 1;
+    }
+}
+
+/// Timing of all transactions with one function code.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FunctionTiming {
+    pub function: u8,
+    pub wait: PhaseStats,
+    pub transmit: PhaseStats,
+    pub first_byte: PhaseStats,
+    pub completion: PhaseStats,
+    pub total: PhaseStats,
+
+    /// Number of transactions by total time, see [`HISTOGRAM_BOUNDS`]
+    pub histogram: [u32; HISTOGRAM_BOUNDS.len() + 1],
+}
+
+/// Timing of the transactions that received a response, by function code.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Default)]
+pub struct TimingStats {
+    functions: [Option<FunctionTiming>; FUNCTIONS],
+
+    /// The most recent transaction
+    pub last: Option<TransactionTiming>,
+
+    /// Number of transactions not recorded because every function code slot was in use
+    pub untracked: u32,
+}
+
+impl TimingStats {
+    pub fn iter(&self) -> impl Iterator<Item = &FunctionTiming> {
+        self.functions.iter().flatten()
+    }
+
+    pub fn function(&self, function: u8) -> Option<&FunctionTiming> {
+        self.iter().find(|f| f.function == function)
+    }
+
+    /// Logs a summary of the timing of each function code
+    ///
+    /// Does nothing unless the `defmt` or `log` feature is enabled.
+    pub fn log(&self) {
+        for f in self.iter() {
+            info!(
+                "FC {:#04x}: {} transactions, total min/avg/max {}/{}/{} us, first byte avg {} us, completion avg {} us",
+                f.function,
+                f.total.count,
+                f.total.min.as_micros(),
+                f.total.mean().unwrap_or_default().as_micros(),
+                f.total.max.as_micros(),
+                f.first_byte.mean().unwrap_or_default().as_micros(),
+                f.completion.mean().unwrap_or_default().as_micros(),
+            );
+            info!("FC {:#04x}: histogram {:?}", f.function, f.histogram);
+        }
+    }
+
+    pub(super) fn record(&mut self, function: u8, timing: TransactionTiming) {
+        self.last = Some(timing);
+
+        let slot = match self
+            .functions
+            .iter()
+            .position(|f| f.is_some_and(|f| f.function == function))
+            .or_else(|| self.functions.iter().position(Option::is_none))
+        {
+            Some(slot) => slot,
+            None => {
+                self.untracked += 1;
+                return;
+            }
+        };
+
+        let f = self.functions[slot].get_or_insert(FunctionTiming {
+            function,
+            ..Default::default()
+        });
+
+        f.wait.record(timing.wait);
+        f.transmit.record(timing.transmit);
+        f.first_byte.record(timing.first_byte);
+        f.completion.record(timing.completion);
+
+        let total = timing.total();
+        f.total.record(total);
+
+        let bucket = HISTOGRAM_BOUNDS
+            .iter()
+            .position(|bound| total <= *bound)
+            .unwrap_or(HISTOGRAM_BOUNDS.len());
+        f.histogram[bucket] += 1;
+    }
+}
+
+impl<I: embedded_io_async::Read + embedded_io_async::Write, const N: usize> Motor<I, N> {
+    pub fn timing(&self) -> &TimingStats {
+        &self.timing
+    }
+
+    pub fn reset_timing(&mut self) {
+        self.timing = Default::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        ReceiveMode,
+        testing::{TestBus, motor},
+    };
+    use embassy_futures::block_on;
+    use embassy_time::Duration;
+
+    #[test]
+    fn first_byte_is_timed_after_the_echo() {
+        for mode in [ReceiveMode::FrameLength, ReceiveMode::Gap] {
+            let mut bus = TestBus::new();
+            bus.echo = true;
+            bus.delay = Duration::from_millis(20);
+
+            let mut motor = motor(bus);
+            motor.set_receive_mode(mode);
+            motor.set_local_echo(true);
+            block_on(motor.temperature()).unwrap();
+
+            // The echo is received straight away, the response half way through the delay at
+            // the earliest, even if the test is slow to send the request
+            let timing = motor.timing().last.unwrap();
+            assert!(
+                timing.first_byte >= Duration::from_millis(10),
+                "{mode:?}: {timing:?}"
+            );
+        }
+    }
+}
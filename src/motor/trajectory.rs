@@ -0,0 +1,96 @@
+use super::Motor;
+use crate::{Result, Trajectory};
+use embassy_time::{Duration, Ticker};
+
+/// The custom command used to send trajectory setpoints.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetpointCommand {
+    /// Function code 0x78, see [`Motor::set_target_position_custom`]
+    TargetPosition,
+
+    /// Function code 0x7B, see [`Motor::set_absolute_position_custom`]
+    AbsolutePosition,
+}
+
+/// Comparison between the commanded and measured path of a followed [`Trajectory`].
+///
+/// Errors are measured position minus commanded position, in steps.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Default)]
+pub struct TrackingReport {
+    /// Number of setpoints sent
+    pub setpoints: u32,
+
+    /// Number of times the position was measured
+    pub samples: u32,
+
+    /// Largest absolute difference between the commanded and measured position
+    pub max_error: u32,
+
+    /// Mean absolute difference between the commanded and measured position
+    pub mean_error: f32,
+
+    /// Difference between the commanded and measured position of the last sample
+    pub last_error: i32,
+}
+
+impl<I: embedded_io_async::Read + embedded_io_async::Write, const N: usize> Motor<I, N> {
+    /// Streams the setpoints of a trajectory to the motor at a fixed tick interval
+    ///
+    /// Every `measure_every` setpoints the absolute position is read back and compared with the
+    /// setpoint that was just sent, 0 disables measurement.
+    /// The tick must be long enough to fit the setpoint (and measurement) transactions.
+    /// Every setpoint is checked against the soft limits, if they reject commands then the target
+    /// is checked before the move starts.
+    pub async fn follow_trajectory(
+        &mut self,
+        trajectory: &Trajectory,
+        command: SetpointCommand,
+        tick: Duration,
+        measure_every: u32,
+    ) -> Result<TrackingReport> {
+        // Positions along the trajectory are all between the start and target, so this is enough
+        // to avoid stopping part way through a move that is going to be rejected
+        self.limit_position(trajectory.target())?;
+
+        let mut report = TrackingReport::default();
+        let mut total_error = 0u64;
+
+        let mut ticker = Ticker::every(tick);
+
+        for setpoint in trajectory.setpoints(tick) {
+            ticker.next().await;
+
+            let setpoint = self.limit_position(setpoint)?;
+
+            match command {
+                SetpointCommand::TargetPosition => {
+                    self.set_target_position_custom(setpoint).await?
+                }
+                SetpointCommand::AbsolutePosition => {
+                    self.set_absolute_position_custom(setpoint).await?
+                }
+            }
+
+            report.setpoints += 1;
+
+            if measure_every != 0 && report.setpoints % measure_every == 0 {
+                let actual = self.absolute_position().await?;
+                let error = actual.wrapping_sub(setpoint) as i32;
+                debug!("pos req/act: {}/{} (diff {})", setpoint, actual, error);
+
+                report.samples += 1;
+                report.max_error = report.max_error.max(error.unsigned_abs());
+                report.last_error = error;
+                total_error += error.unsigned_abs() as u64;
+            }
+        }
+
+        if report.samples > 0 {
+            report.mean_error = total_error as f32 / report.samples as f32;
+        }
+
+        Ok(report)
+    }
+}
@@ -0,0 +1,446 @@
+use crate::{Error, Motor, MotorStatus, Result, WriteBatch, WriteReport};
+use core::cell::RefCell;
+use embassy_sync::{
+    blocking_mutex::{self, raw::RawMutex},
+    signal::Signal,
+};
+use embassy_time::{Duration, Instant};
+
+/// Order in which queued commands are sent, highest priority first.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Setpoint,
+    Telemetry,
+    Config,
+}
+
+impl Priority {
+    const ALL: [Self; 3] = [Self::Setpoint, Self::Telemetry, Self::Config];
+}
+
+/// A request to be sent to a motor by a [`CommandQueue`].
+#[derive(Debug, Clone)]
+pub enum Command {
+    /// Sets the target speed, replacing a queued target speed that has not been sent yet
+    TargetRpm(u16),
+
+    /// Sets the target position, replacing a queued target position that has not been sent yet
+    TargetPosition(u32),
+
+    /// Reads the status registers
+    Status,
+
+    /// Reads the absolute position
+    AbsolutePosition,
+
+    /// Writes a batch of parameters
+    Write(WriteBatch),
+}
+
+impl Command {
+    pub fn priority(&self) -> Priority {
+        match self {
+            Self::TargetRpm(_) | Self::TargetPosition(_) => Priority::Setpoint,
+            Self::Status | Self::AbsolutePosition => Priority::Telemetry,
+            Self::Write(_) => Priority::Config,
+        }
+    }
+
+    // If a newer command replaces this one
+    fn replaced_by(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::TargetRpm(_), Self::TargetRpm(_))
+                | (Self::TargetPosition(_), Self::TargetPosition(_))
+        )
+    }
+}
+
+/// The result of a [`Command`].
+#[derive(Debug, Clone)]
+pub enum Reply {
+    Done,
+
+    /// The setpoint was replaced by a newer one before it was sent, and never will be
+    Replaced,
+
+    Status(MotorStatus),
+    Position(u32),
+    Batch(WriteReport),
+}
+
+/// Time from submitting commands to them being completed.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LatencyStats {
+    pub count: u32,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+impl LatencyStats {
+    pub fn mean(&self) -> Option<Duration> {
+        (self.count > 0).then(|| self.total / self.count)
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy)]
+pub struct QueueStats {
+    /// When the statistics were last reset
+    pub since: Instant,
+
+    /// Time spent executing commands
+    pub busy: Duration,
+
+    /// Number of commands executed, including those that failed
+    pub executed: u32,
+
+    /// Number of commands that failed
+    pub failed: u32,
+
+    /// Number of setpoints dropped because a newer one was submitted before they were sent
+    pub replaced: u32,
+
+    /// Number of commands rejected because the queue was full
+    pub rejected: u32,
+
+    /// Latency of each priority, indexed by [`Priority`]
+    pub latency: [LatencyStats; 3],
+}
+
+impl QueueStats {
+    fn new() -> Self {
+        Self {
+            since: Instant::now(),
+            busy: Duration::from_ticks(0),
+            executed: 0,
+            failed: 0,
+            replaced: 0,
+            rejected: 0,
+            latency: [LatencyStats::default(); 3],
+        }
+    }
+
+    pub fn latency(&self, priority: Priority) -> &LatencyStats {
+        &self.latency[priority as usize]
+    }
+
+    /// Fraction of the time since the statistics were reset that the bus was busy, 0-1
+    pub fn utilisation(&self) -> f32 {
+        let elapsed = Instant::now() - self.since;
+        if elapsed.as_ticks() == 0 {
+            0.
+        } else {
+            self.busy.as_ticks() as f32 / elapsed.as_ticks() as f32
+        }
+    }
+}
+
+struct Entry {
+    command: Command,
+    submitted: Instant,
+    // Commands of the same priority are sent in the order they were submitted
+    sequence: u32,
+}
+
+enum SlotState {
+    Free,
+    Queued(Entry),
+    Running,
+    Done(Result<Reply>),
+}
+
+struct Slot {
+    state: SlotState,
+    // The ticket the slot belongs to, a replaced setpoint's ticket no longer matches
+    ticket: u32,
+    // If the ticket was dropped, so the result is not kept
+    detached: bool,
+}
+
+struct State<const N: usize> {
+    slots: [Slot; N],
+    sequence: u32,
+    stats: QueueStats,
+}
+
+/// Prioritised commands waiting to be sent to a motor.
+///
+/// Any task can submit commands, which are sent by the task calling
+/// [`CommandQueue::execute_next`].
+/// Setpoints are sent before telemetry, which is sent before configuration.
+/// Commands are sent back to back, only waiting for the inter-frame delay after each response.
+///
+/// The result of each command is delivered to the [`Ticket`] returned when it was submitted.
+/// A command holds its place in the queue until its ticket has the result, or is dropped.
+pub struct CommandQueue<M: RawMutex, const N: usize> {
+    state: blocking_mutex::Mutex<M, RefCell<State<N>>>,
+    submitted: Signal<M, ()>,
+    // Signalled when the command in the slot with the same index changes state
+    completed: [Signal<M, ()>; N],
+}
+
+impl<M: RawMutex, const N: usize> Default for CommandQueue<M, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M: RawMutex, const N: usize> CommandQueue<M, N> {
+    pub fn new() -> Self {
+        Self {
+            state: blocking_mutex::Mutex::new(RefCell::new(State {
+                slots: [const {
+                    Slot {
+                        state: SlotState::Free,
+                        ticket: 0,
+                        detached: false,
+                    }
+                }; N],
+                sequence: 0,
+                stats: QueueStats::new(),
+            })),
+            submitted: Signal::new(),
+            completed: [const { Signal::new() }; N],
+        }
+    }
+
+    /// Adds a command to the queue, returning a ticket for its result
+    ///
+    /// A setpoint replaces a queued setpoint of the same kind, which is never sent, and whose
+    /// ticket gets [`Reply::Replaced`].
+    /// Dropping the ticket does not cancel the command, only its result is discarded.
+    pub fn submit(&self, command: Command) -> Result<Ticket<'_, M, N>> {
+        let result = self.state.lock(|s| {
+            let mut s = s.borrow_mut();
+            let s = &mut *s;
+
+            let ticket = s.sequence;
+            s.sequence = s.sequence.wrapping_add(1);
+
+            if let Some((index, slot)) = s.slots.iter_mut().enumerate().find(|(_, slot)| {
+                matches!(&slot.state, SlotState::Queued(e) if e.command.replaced_by(&command))
+            }) && let SlotState::Queued(entry) = &mut slot.state
+            {
+                debug!("Replacing queued setpoint");
+                entry.command = command;
+                // Latency is measured from the newest setpoint, the replaced one is never sent
+                entry.submitted = Instant::now();
+                slot.ticket = ticket;
+                slot.detached = false;
+                s.stats.replaced += 1;
+                return Ok((index, ticket, true));
+            }
+
+            match s
+                .slots
+                .iter_mut()
+                .enumerate()
+                .find(|(_, slot)| matches!(slot.state, SlotState::Free))
+            {
+                Some((index, slot)) => {
+                    *slot = Slot {
+                        state: SlotState::Queued(Entry {
+                            command,
+                            submitted: Instant::now(),
+                            sequence: ticket,
+                        }),
+                        ticket,
+                        detached: false,
+                    };
+                    Ok((index, ticket, false))
+                }
+                None => {
+                    s.stats.rejected += 1;
+                    Err(Error::QueueFull)
+                }
+            }
+        });
+
+        let (index, ticket, replaced) = result?;
+
+        // Wakes the replaced setpoint's ticket, if it is being waited on
+        if replaced {
+            self.completed[index].signal(());
+        }
+        self.submitted.signal(());
+
+        Ok(Ticket {
+            queue: self,
+            index,
+            ticket,
+        })
+    }
+
+    /// Number of commands waiting to be sent
+    pub fn len(&self) -> usize {
+        self.state.lock(|s| {
+            s.borrow()
+                .slots
+                .iter()
+                .filter(|slot| matches!(slot.state, SlotState::Queued(_)))
+                .count()
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn stats(&self) -> QueueStats {
+        self.state.lock(|s| s.borrow().stats)
+    }
+
+    pub fn reset_stats(&self) {
+        self.state
+            .lock(|s| s.borrow_mut().stats = QueueStats::new());
+    }
+
+    /// Waits for a command and sends it to the motor
+    ///
+    /// The result is delivered to the command's [`Ticket`].
+    pub async fn execute_next<
+        I: embedded_io_async::Read + embedded_io_async::Write,
+        const B: usize,
+    >(
+        &self,
+        motor: &mut Motor<I, B>,
+    ) {
+        let (index, ticket, entry) = loop {
+            if let Some(next) = self.take_next() {
+                break next;
+            }
+            self.submitted.wait().await;
+        };
+
+        let started = Instant::now();
+
+        let result = match &entry.command {
+            Command::TargetRpm(rpm) => motor.set_target_rpm(*rpm).await.map(|_| Reply::Done),
+            Command::TargetPosition(position) => motor
+                .set_target_position(*position)
+                .await
+                .map(|_| Reply::Done),
+            Command::Status => motor.status().await.map(Reply::Status),
+            Command::AbsolutePosition => motor.absolute_position().await.map(Reply::Position),
+            Command::Write(batch) => motor.write_batch(batch).await.map(Reply::Batch),
+        };
+
+        let finished = Instant::now();
+        let priority = entry.command.priority();
+
+        if let Err(e) = &result {
+            warn!("Queued {:?} command failed: {}", priority, e);
+        }
+
+        self.state.lock(|s| {
+            let mut s = s.borrow_mut();
+            let s = &mut *s;
+
+            let stats = &mut s.stats;
+            stats.busy += finished - started;
+            stats.executed += 1;
+            if result.is_err() {
+                stats.failed += 1;
+            }
+
+            let latency = finished - entry.submitted;
+            let l = &mut stats.latency[priority as usize];
+            l.count += 1;
+            l.total += latency;
+            l.max = l.max.max(latency);
+
+            // A running command cannot be replaced, so the slot still belongs to its ticket
+            let slot = &mut s.slots[index];
+            debug_assert_eq!(slot.ticket, ticket);
+            slot.state = if slot.detached {
+                SlotState::Free
+            } else {
+                SlotState::Done(result)
+            };
+        });
+
+        self.completed[index].signal(());
+    }
+
+    fn take_next(&self) -> Option<(usize, u32, Entry)> {
+        self.state.lock(|s| {
+            let mut s = s.borrow_mut();
+
+            let next = Priority::ALL.into_iter().find_map(|priority| {
+                s.slots
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, slot)| match &slot.state {
+                        SlotState::Queued(e) if e.command.priority() == priority => Some((i, e)),
+                        _ => None,
+                    })
+                    .min_by_key(|(_, e)| e.sequence)
+                    .map(|(i, _)| i)
+            })?;
+
+            let slot = &mut s.slots[next];
+            match core::mem::replace(&mut slot.state, SlotState::Running) {
+                SlotState::Queued(entry) => Some((next, slot.ticket, entry)),
+                _ => unreachable!(),
+            }
+        })
+    }
+}
+
+/// The pending result of a command submitted to a [`CommandQueue`].
+///
+/// Dropping the ticket discards the result, the command is still sent.
+pub struct Ticket<'a, M: RawMutex, const N: usize> {
+    queue: &'a CommandQueue<M, N>,
+    index: usize,
+    ticket: u32,
+}
+
+impl<M: RawMutex, const N: usize> Ticket<'_, M, N> {
+    /// Waits for the command to be sent, and gets its result
+    pub async fn wait(self) -> Result<Reply> {
+        loop {
+            let result = self.queue.state.lock(|s| {
+                let slot = &mut s.borrow_mut().slots[self.index];
+
+                if slot.ticket != self.ticket {
+                    return Some(Ok(Reply::Replaced));
+                }
+
+                match core::mem::replace(&mut slot.state, SlotState::Free) {
+                    SlotState::Done(result) => Some(result),
+                    state => {
+                        slot.state = state;
+                        None
+                    }
+                }
+            });
+
+            if let Some(result) = result {
+                return result;
+            }
+
+            self.queue.completed[self.index].wait().await;
+        }
+    }
+}
+
+impl<M: RawMutex, const N: usize> Drop for Ticket<'_, M, N> {
+    fn drop(&mut self) {
+        self.queue.state.lock(|s| {
+            let slot = &mut s.borrow_mut().slots[self.index];
+
+            if slot.ticket == self.ticket {
+                match slot.state {
+                    SlotState::Done(_) => slot.state = SlotState::Free,
+                    SlotState::Queued(_) | SlotState::Running => slot.detached = true,
+                    // The result was already taken
+                    SlotState::Free => {}
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,150 @@
+use crate::{AlarmCode, Motor, MotorDriver, MotorStatus, Result};
+use core::{
+    cell::RefCell,
+    ops::{Deref, DerefMut},
+};
+use embassy_sync::{
+    blocking_mutex::{self, raw::RawMutex},
+    mutex::{Mutex, MutexGuard},
+};
+use embassy_time::Instant;
+
+/// A [`Motor`] shared between tasks.
+///
+/// [`SharedMotor::lock`] gives a task exclusive access to the motor, so that a sequence of
+/// requests is not interleaved with requests from other tasks.
+/// The most recently read status is cached and can be read by any task without waiting for the
+/// motor, see [`SharedMotor::cached_status`].
+///
+/// `&SharedMotor` implements [`MotorDriver`], locking the motor for each call.
+pub struct SharedMotor<
+    M: RawMutex,
+    I: embedded_io_async::Read + embedded_io_async::Write,
+    const N: usize = 64,
+> {
+    motor: Mutex<M, Motor<I, N>>,
+    status: blocking_mutex::Mutex<M, RefCell<Option<(MotorStatus, Instant)>>>,
+}
+
+impl<M: RawMutex, I: embedded_io_async::Read + embedded_io_async::Write, const N: usize>
+    SharedMotor<M, I, N>
+{
+    pub fn new(motor: Motor<I, N>) -> Self {
+        Self {
+            motor: Mutex::new(motor),
+            status: blocking_mutex::Mutex::new(RefCell::new(None)),
+        }
+    }
+
+    /// Waits for exclusive access to the motor
+    ///
+    /// Other tasks wait until the guard is dropped, so it should not be held while waiting for
+    /// anything other than the motor.
+    pub async fn lock(&self) -> SharedMotorGuard<'_, M, I, N> {
+        SharedMotorGuard {
+            motor: self.motor.lock().await,
+            shared: self,
+        }
+    }
+
+    /// Gets exclusive access to the motor if no other task has it
+    pub fn try_lock(&self) -> Option<SharedMotorGuard<'_, M, I, N>> {
+        Some(SharedMotorGuard {
+            motor: self.motor.try_lock().ok()?,
+            shared: self,
+        })
+    }
+
+    /// Gets the most recently read status, and when it was read, without locking the motor
+    ///
+    /// The status is updated whenever it is read through a [`SharedMotorGuard`], by
+    /// [`SharedMotor::refresh_status`], or by a [`Monitor`](crate::Monitor) polling `&SharedMotor`.
+    pub fn cached_status(&self) -> Option<(MotorStatus, Instant)> {
+        self.status.lock(|s| s.borrow().clone())
+    }
+
+    /// Reads the status of the motor, updating the cached status
+    pub async fn refresh_status(&self) -> Result<MotorStatus> {
+        self.lock().await.status().await
+    }
+
+    pub fn into_inner(self) -> Motor<I, N> {
+        self.motor.into_inner()
+    }
+}
+
+/// Exclusive access to a [`SharedMotor`], the motor is released when the guard is dropped.
+pub struct SharedMotorGuard<
+    'a,
+    M: RawMutex,
+    I: embedded_io_async::Read + embedded_io_async::Write,
+    const N: usize,
+> {
+    motor: MutexGuard<'a, M, Motor<I, N>>,
+    shared: &'a SharedMotor<M, I, N>,
+}
+
+impl<M: RawMutex, I: embedded_io_async::Read + embedded_io_async::Write, const N: usize>
+    SharedMotorGuard<'_, M, I, N>
+{
+    /// Reads the status of the motor, updating the cached status
+    pub async fn status(&mut self) -> Result<MotorStatus> {
+        let status = self.motor.status().await?;
+
+        self.shared
+            .status
+            .lock(|s| *s.borrow_mut() = Some((status.clone(), Instant::now())));
+
+        Ok(status)
+    }
+}
+
+impl<M: RawMutex, I: embedded_io_async::Read + embedded_io_async::Write, const N: usize> Deref
+    for SharedMotorGuard<'_, M, I, N>
+{
+    type Target = Motor<I, N>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.motor
+    }
+}
+
+impl<M: RawMutex, I: embedded_io_async::Read + embedded_io_async::Write, const N: usize> DerefMut
+    for SharedMotorGuard<'_, M, I, N>
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.motor
+    }
+}
+
+impl<M: RawMutex, I: embedded_io_async::Read + embedded_io_async::Write, const N: usize> MotorDriver
+    for &SharedMotor<M, I, N>
+{
+    async fn set_enabled(&mut self, enabled: bool) -> Result<()> {
+        self.lock().await.set_drive_enabled(enabled).await
+    }
+
+    async fn set_velocity(&mut self, rpm: u16) -> Result<()> {
+        self.lock().await.set_target_rpm(rpm).await
+    }
+
+    async fn move_to(&mut self, position: u32) -> Result<()> {
+        self.lock().await.set_target_position(position).await
+    }
+
+    async fn position(&mut self) -> Result<u32> {
+        self.lock().await.absolute_position().await
+    }
+
+    async fn speed(&mut self) -> Result<f32> {
+        self.lock().await.speed().await
+    }
+
+    async fn status(&mut self) -> Result<MotorStatus> {
+        self.refresh_status().await
+    }
+
+    async fn fault(&mut self) -> Result<Option<AlarmCode>> {
+        self.lock().await.alarm_code().await
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{Motor, Result};
+use embassy_time::{Duration, Instant};
+
+/// Thresholds used by a [`Supervisor`], `None` disables a check.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone)]
+pub struct SupervisorConfig {
+    /// Maximum difference in steps between the commanded and measured position
+    pub max_following_error: Option<u32>,
+
+    /// Maximum difference in RPM between the commanded and measured speed
+    pub max_speed_error: Option<f32>,
+
+    /// Speed in RPM below which the motor is considered stalled when it is commanded to move
+    pub stall_speed: Option<f32>,
+
+    /// Maximum current in A
+    pub max_current: Option<f32>,
+
+    /// How long a threshold must be continuously exceeded before the supervisor trips
+    pub window: Duration,
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultKind {
+    FollowingError,
+    SpeedError,
+    Stall,
+    Overcurrent,
+}
+
+/// The state of the motor at the time a [`Supervisor`] tripped.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone)]
+pub struct Fault {
+    pub kind: FaultKind,
+
+    pub commanded_position: u32,
+    pub measured_position: u32,
+
+    pub commanded_speed: f32,
+    pub measured_speed: f32,
+
+    pub current: f32,
+
+    /// If the drive was successfully disabled
+    pub drive_disabled: bool,
+}
+
+/// Watches for a motor failing to follow its commands and disables the drive when it does.
+///
+/// [`Supervisor::check`] must be called regularly (at least a few times per window) with the
+/// position and speed that the motor is currently being commanded to.
+pub struct Supervisor {
+    config: SupervisorConfig,
+
+    // When each threshold started being exceeded
+    following_error_since: Option<Instant>,
+    speed_error_since: Option<Instant>,
+    stall_since: Option<Instant>,
+    overcurrent_since: Option<Instant>,
+}
+
+impl Supervisor {
+    pub fn new(config: SupervisorConfig) -> Self {
+        Self {
+            config,
+            following_error_since: None,
+            speed_error_since: None,
+            stall_since: None,
+            overcurrent_since: None,
+        }
+    }
+
+    /// Forgets any thresholds that are currently exceeded, e.g. after recovering from a fault
+    pub fn reset(&mut self) {
+        self.following_error_since = None;
+        self.speed_error_since = None;
+        self.stall_since = None;
+        self.overcurrent_since = None;
+    }
+
+    /// Compares the state of the motor with what it is commanded to do
+    ///
+    /// If a threshold has been exceeded for longer than the window then the drive is disabled
+    /// and the fault is returned.
+    pub async fn check<I: embedded_io_async::Read + embedded_io_async::Write, const N: usize>(
+        &mut self,
+        motor: &mut Motor<I, N>,
+        commanded_position: u32,
+        commanded_speed: f32,
+    ) -> Result<Option<Fault>> {
+        let measured_position = motor.absolute_position().await?;
+        let measured_speed = motor.speed().await?;
+        let current = motor.current().await?;
+
+        let now = Instant::now();
+        let window = self.config.window;
+
+        let tripped = |since: &mut Option<Instant>, exceeded: bool| {
+            if exceeded {
+                now - *since.get_or_insert(now) >= window
+            } else {
+                *since = None;
+                false
+            }
+        };
+
+        let overcurrent = tripped(
+            &mut self.overcurrent_since,
+            self.config.max_current.is_some_and(|max| current > max),
+        );
+
+        let stall = tripped(
+            &mut self.stall_since,
+            self.config
+                .stall_speed
+                .is_some_and(|min| commanded_speed >= min && measured_speed < min),
+        );
+
+        let following_error = tripped(
+            &mut self.following_error_since,
+            self.config
+                .max_following_error
+                .is_some_and(|max| measured_position.abs_diff(commanded_position) > max),
+        );
+
+        let speed_error = tripped(
+            &mut self.speed_error_since,
+            self.config
+                .max_speed_error
+                .is_some_and(|max| (measured_speed - commanded_speed).abs() > max),
+        );
+
+        let kind = if overcurrent {
+            FaultKind::Overcurrent
+        } else if stall {
+            FaultKind::Stall
+        } else if following_error {
+            FaultKind::FollowingError
+        } else if speed_error {
+            FaultKind::SpeedError
+        } else {
+            return Ok(None);
+        };
+
+        let drive_disabled = motor.set_drive_enabled(false).await.is_ok();
+
+        let fault = Fault {
+            kind,
+            commanded_position,
+            measured_position,
+            commanded_speed,
+            measured_speed,
+            current,
+            drive_disabled,
+        };
+        error!("Supervisor tripped: {:?}", fault);
+
+        self.reset();
+
+        Ok(Some(fault))
+    }
+}
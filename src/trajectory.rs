@@ -0,0 +1,330 @@
+use crate::{Error, Result};
+use embassy_time::Duration;
+
+/// Kinematic limits used to plan a [`Trajectory`].
+///
+/// All values are in position steps (the same units as [`crate::Motor::absolute_position`]).
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone)]
+pub struct TrajectoryLimits {
+    /// Maximum velocity in steps/s
+    pub max_velocity: f32,
+
+    /// Maximum acceleration in steps/s^2
+    pub max_acceleration: f32,
+
+    /// Maximum jerk in steps/s^3
+    ///
+    /// `None` gives a trapezoidal profile, otherwise an S-curve profile is planned.
+    pub max_jerk: Option<f32>,
+}
+
+/// A point to point motion profile.
+///
+/// The profile is symmetric: accelerate, cruise at the peak velocity (if the move is long enough
+/// to reach it), then decelerate to a stop at the target.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone)]
+pub struct Trajectory {
+    start: u32,
+    target: u32,
+    steps: u32,
+
+    // The kinematics are planned in f64, which represents every distance exactly, and in which
+    // the rounding of a position is well below one step even for the longest moves
+    peak_velocity: f64,
+
+    // Duration of each jerk limited section of the acceleration phase
+    t_j: f64,
+    // Duration of the entire acceleration phase
+    t_a: f64,
+    // Duration of the constant velocity phase
+    t_v: f64,
+}
+
+impl Trajectory {
+    /// Plans a move from `start` to `target`
+    ///
+    /// Fails with [`Error::InvalidArgument`] if any of the limits is not a positive, finite number.
+    pub fn new(start: u32, target: u32, limits: &TrajectoryLimits) -> Result<Self> {
+        let valid = |v: f32| v.is_finite() && v > 0.;
+
+        if !valid(limits.max_velocity)
+            || !valid(limits.max_acceleration)
+            || !limits.max_jerk.is_none_or(valid)
+        {
+            return Err(Error::InvalidArgument);
+        }
+
+        let steps = start.abs_diff(target);
+        let distance = steps as f64;
+
+        let mut peak_velocity = limits.max_velocity as f64;
+        let (mut t_j, mut t_a) = acceleration_time(peak_velocity, limits);
+
+        // The move is too short to reach the maximum velocity, find the highest velocity that
+        // still allows the acceleration and deceleration phases to fit within the distance
+        if peak_velocity * t_a > distance {
+            let mut low = 0.;
+            let mut high = limits.max_velocity as f64;
+
+            for _ in 0..64 {
+                let mid = (low + high) / 2.;
+                let (_, t_a) = acceleration_time(mid, limits);
+
+                if mid * t_a > distance {
+                    high = mid;
+                } else {
+                    low = mid;
+                }
+            }
+
+            peak_velocity = low;
+            (t_j, t_a) = acceleration_time(peak_velocity, limits);
+        }
+
+        let t_v = if peak_velocity > 0. {
+            (distance / peak_velocity - t_a).max(0.)
+        } else {
+            0.
+        };
+
+        Ok(Self {
+            start,
+            target,
+            steps,
+            peak_velocity,
+            t_j,
+            t_a,
+            t_v,
+        })
+    }
+
+    /// Gets the position the move starts from
+    pub fn start(&self) -> u32 {
+        self.start
+    }
+
+    /// Gets the position the move ends at
+    pub fn target(&self) -> u32 {
+        self.target
+    }
+
+    /// Gets the highest velocity reached during the move in steps/s
+    pub fn peak_velocity(&self) -> f32 {
+        self.peak_velocity as f32
+    }
+
+    /// Gets the time taken to complete the move
+    pub fn duration(&self) -> Duration {
+        Duration::from_micros((self.total_time() * 1_000_000.) as u64)
+    }
+
+    /// Gets the commanded position at a given time since the start of the move
+    pub fn position_at(&self, t: Duration) -> u32 {
+        let t = seconds(t);
+        let total = self.total_time();
+
+        if t >= total {
+            return self.target;
+        }
+
+        let travelled = if t <= 0. {
+            0.
+        } else if t <= self.t_a {
+            self.ramp(t)
+        } else if t <= self.t_a + self.t_v {
+            self.peak_velocity * (self.t_a / 2. + (t - self.t_a))
+        } else {
+            self.steps as f64 - self.ramp(total - t)
+        };
+
+        // Only the distance from the start is computed in floating point, and it is clamped so
+        // that rounding can never take a setpoint past the target
+        let travelled = (libm::round(travelled.max(0.)) as u32).min(self.steps);
+
+        if self.target >= self.start {
+            self.start + travelled
+        } else {
+            self.start - travelled
+        }
+    }
+
+    /// Gets the sequence of setpoints to send at a fixed tick interval
+    ///
+    /// The first setpoint is the position one tick after the start of the move, the last is
+    /// always the target position.
+    pub fn setpoints(&self, tick: Duration) -> Setpoints<'_> {
+        Setpoints {
+            trajectory: self,
+            tick,
+            elapsed: Duration::from_ticks(0),
+            done: false,
+        }
+    }
+
+    fn total_time(&self) -> f64 {
+        2. * self.t_a + self.t_v
+    }
+
+    // Distance travelled a given time into the acceleration phase.
+    fn ramp(&self, t: f64) -> f64 {
+        if self.t_a <= 0. {
+            0.
+        } else if self.t_j <= 0. {
+            let a = self.peak_velocity / self.t_a;
+            a * t * t / 2.
+        } else {
+            let a = self.peak_velocity / (self.t_a - self.t_j);
+            let j = a / self.t_j;
+
+            if t < self.t_j {
+                j * t * t * t / 6.
+            } else if t <= self.t_a - self.t_j {
+                let t = t - self.t_j;
+                let p = j * self.t_j * self.t_j * self.t_j / 6.;
+                let v = a * self.t_j / 2.;
+                p + v * t + a * t * t / 2.
+            } else {
+                // The velocity curve of the ramp is symmetric about its midpoint
+                self.peak_velocity * (t - self.t_a / 2.) + self.ramp(self.t_a - t)
+            }
+        }
+    }
+}
+
+/// Iterator over the setpoints of a [`Trajectory`], see [`Trajectory::setpoints`].
+pub struct Setpoints<'a> {
+    trajectory: &'a Trajectory,
+    tick: Duration,
+    elapsed: Duration,
+    done: bool,
+}
+
+impl Iterator for Setpoints<'_> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        self.elapsed += self.tick;
+
+        if self.elapsed >= self.trajectory.duration() || self.tick.as_ticks() == 0 {
+            self.done = true;
+            Some(self.trajectory.target)
+        } else {
+            Some(self.trajectory.position_at(self.elapsed))
+        }
+    }
+}
+
+// Get the jerk section and total duration of the phase accelerating from rest to a velocity.
+fn acceleration_time(velocity: f64, limits: &TrajectoryLimits) -> (f64, f64) {
+    let a = limits.max_acceleration as f64;
+
+    match limits.max_jerk.map(f64::from) {
+        None => (0., velocity / a),
+        Some(j) => {
+            if velocity * j >= a * a {
+                // Maximum acceleration is reached
+                (a / j, a / j + velocity / a)
+            } else {
+                let t_j = libm::sqrt(velocity / j);
+                (t_j, 2. * t_j)
+            }
+        }
+    }
+}
+
+fn seconds(t: Duration) -> f64 {
+    t.as_micros() as f64 / 1_000_000.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK: Duration = Duration::from_millis(1);
+
+    fn limits(max_jerk: Option<f32>) -> TrajectoryLimits {
+        TrajectoryLimits {
+            max_velocity: 400_000.,
+            max_acceleration: 1_000_000.,
+            max_jerk,
+        }
+    }
+
+    // Checks the setpoints move monotonically towards the target, end exactly on it, and never
+    // step further than the peak velocity allows
+    fn check(start: u32, target: u32, limits: &TrajectoryLimits) {
+        let trajectory = Trajectory::new(start, target, limits).unwrap();
+        let max_step = (trajectory.peak_velocity() as f64 * 1e-3).ceil() as u32 + 1;
+
+        let mut previous = start;
+        let mut count = 0;
+        for setpoint in trajectory.setpoints(TICK) {
+            if target >= start {
+                assert!(
+                    (previous..=target).contains(&setpoint),
+                    "{previous} -> {setpoint}"
+                );
+            } else {
+                assert!(
+                    (target..=previous).contains(&setpoint),
+                    "{previous} -> {setpoint}"
+                );
+            }
+            assert!(
+                setpoint.abs_diff(previous) <= max_step,
+                "{previous} -> {setpoint}, more than {max_step}"
+            );
+            previous = setpoint;
+            count += 1;
+        }
+
+        assert_eq!(previous, target);
+        assert!(count >= 1);
+    }
+
+    #[test]
+    fn trapezoidal_moves_end_on_target() {
+        for (start, target) in [(0, 1000), (1000, 0), (0, 1_000_000), (5, 6), (7, 7)] {
+            check(start, target, &limits(None));
+        }
+    }
+
+    #[test]
+    fn s_curve_moves_end_on_target() {
+        for (start, target) in [(0, 1000), (1000, 0), (0, 1_000_000), (5, 6)] {
+            check(start, target, &limits(Some(50_000_000.)));
+        }
+    }
+
+    #[test]
+    fn full_range_moves_are_smooth() {
+        check(0, u32::MAX, &limits(None));
+        check(u32::MAX, 0, &limits(Some(50_000_000.)));
+        check(u32::MAX - 1000, u32::MAX, &limits(None));
+    }
+
+    #[test]
+    fn short_moves_do_not_reach_the_maximum_velocity() {
+        let trajectory = Trajectory::new(0, 100, &limits(None)).unwrap();
+        assert!(trajectory.peak_velocity() < 400_000.);
+        assert_eq!(trajectory.position_at(trajectory.duration()), 100);
+    }
+
+    #[test]
+    fn invalid_limits_are_rejected() {
+        let mut invalid = limits(None);
+        invalid.max_velocity = 0.;
+        assert_eq!(
+            Trajectory::new(0, 1, &invalid).unwrap_err(),
+            Error::InvalidArgument
+        );
+
+        assert!(Trajectory::new(0, 1, &limits(Some(f32::NAN))).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+use crate::{Error, Result, SerialConfig};
+
+/// A transport whose baud rate can be changed.
+///
+/// For transports from other crates, see [`BaudRateAdapter`].
+pub trait SetBaudRate {
+    fn set_baud_rate(&mut self, baud: u32) -> Result<()>;
+
+    /// Changes the baud rate and character framing
+    ///
+    /// By default only 8N1 is supported, other framings fail with [`Error::InvalidArgument`]
+    /// rather than being silently ignored. Transports that support them should override this.
+    fn set_serial_config(&mut self, config: &SerialConfig) -> Result<()> {
+        if *config != SerialConfig::new(config.baud) {
+            return Err(Error::InvalidArgument);
+        }
+        self.set_baud_rate(config.baud)
+    }
+}
+
+impl<T: SetBaudRate + ?Sized> SetBaudRate for &mut T {
+    fn set_baud_rate(&mut self, baud: u32) -> Result<()> {
+        T::set_baud_rate(self, baud)
+    }
+
+    fn set_serial_config(&mut self, config: &SerialConfig) -> Result<()> {
+        T::set_serial_config(self, config)
+    }
+}
+
+/// Implements [`SetBaudRate`] for a transport using a function that changes its serial
+/// configuration.
+///
+/// The function is given the whole [`SerialConfig`], and should fail with
+/// [`Error::InvalidArgument`] if it cannot apply the parity or stop bits.
+/// A change of baud rate alone keeps the framing last set, or 8N1 if none was.
+///
+/// e.g. `BaudRateAdapter::new(&mut uart, |uart, config| uart.set_config(config))`
+pub struct BaudRateAdapter<I, F> {
+    inner: I,
+    set_serial_config: F,
+    config: Option<SerialConfig>,
+}
+
+impl<I, F: FnMut(&mut I, &SerialConfig) -> Result<()>> BaudRateAdapter<I, F> {
+    pub fn new(inner: I, set_serial_config: F) -> Self {
+        Self {
+            inner,
+            set_serial_config,
+            config: None,
+        }
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+}
+
+impl<I, F: FnMut(&mut I, &SerialConfig) -> Result<()>> SetBaudRate for BaudRateAdapter<I, F> {
+    fn set_baud_rate(&mut self, baud: u32) -> Result<()> {
+        let config = match self.config {
+            Some(config) => SerialConfig { baud, ..config },
+            None => SerialConfig::new(baud),
+        };
+        self.set_serial_config(&config)
+    }
+
+    fn set_serial_config(&mut self, config: &SerialConfig) -> Result<()> {
+        (self.set_serial_config)(&mut self.inner, config)?;
+        self.config = Some(*config);
+        Ok(())
+    }
+}
+
+impl<I: embedded_io_async::ErrorType, F> embedded_io_async::ErrorType for BaudRateAdapter<I, F> {
+    type Error = I::Error;
+}
+
+impl<I: embedded_io_async::Read, F> embedded_io_async::Read for BaudRateAdapter<I, F> {
+    async fn read(&mut self, buf: &mut [u8]) -> core::result::Result<usize, Self::Error> {
+        self.inner.read(buf).await
+    }
+}
+
+impl<I: embedded_io_async::Write, F> embedded_io_async::Write for BaudRateAdapter<I, F> {
+    async fn write(&mut self, buf: &[u8]) -> core::result::Result<usize, Self::Error> {
+        self.inner.write(buf).await
+    }
+
+    async fn flush(&mut self) -> core::result::Result<(), Self::Error> {
+        self.inner.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parity;
+    use std::{vec, vec::Vec};
+
+    struct BaudOnly(u32);
+
+    impl SetBaudRate for BaudOnly {
+        fn set_baud_rate(&mut self, baud: u32) -> Result<()> {
+            self.0 = baud;
+            Ok(())
+        }
+    }
+
+    fn even_parity(baud: u32) -> SerialConfig {
+        SerialConfig {
+            parity: Parity::Even,
+            ..SerialConfig::new(baud)
+        }
+    }
+
+    #[test]
+    fn framing_is_not_silently_ignored() {
+        let mut transport = BaudOnly(0);
+
+        assert_eq!(
+            transport.set_serial_config(&SerialConfig::new(9600)),
+            Ok(())
+        );
+        assert_eq!(
+            transport.set_serial_config(&even_parity(19200)),
+            Err(Error::InvalidArgument)
+        );
+        assert_eq!(transport.0, 9600);
+    }
+
+    #[test]
+    fn adapter_is_given_the_whole_config() {
+        let mut adapter = BaudRateAdapter::new(
+            Vec::new(),
+            |applied: &mut Vec<SerialConfig>, config: &SerialConfig| {
+                applied.push(*config);
+                Ok(())
+            },
+        );
+
+        adapter.set_baud_rate(9600).unwrap();
+        adapter.set_serial_config(&even_parity(19200)).unwrap();
+        adapter.set_baud_rate(38400).unwrap();
+
+        assert_eq!(
+            adapter.into_inner(),
+            vec![
+                SerialConfig::new(9600),
+                even_parity(19200),
+                even_parity(38400)
+            ]
+        );
+    }
+}
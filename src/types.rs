@@ -1,7 +1,7 @@
-use defmt::Format;
 use embassy_time::Duration;
 
-#[derive(Debug, Format, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RtuBaud {
     Baud115200,
     Baud38400,
@@ -10,39 +10,254 @@ pub enum RtuBaud {
 }
 
 impl RtuBaud {
-    // Get the inter-character delay for the baud rate.
-    // See https://www.modbus.org/docs/Modbus_over_serial_line_V1_02.pdf
-    pub(crate) fn t15(&self) -> Duration {
+    /// All baud rates supported by the motor, fastest first
+    pub const ALL: [Self; 4] = [
+        Self::Baud115200,
+        Self::Baud38400,
+        Self::Baud19200,
+        Self::Baud9600,
+    ];
+
+    /// Gets the baud rate in bits per second
+    pub fn rate(&self) -> u32 {
         match self {
-            Self::Baud115200 => Duration::from_micros(750),
-            Self::Baud38400 => Duration::from_micros(750),
-            Self::Baud19200 => Duration::from_micros(1979),
-            Self::Baud9600 => Duration::from_micros(3958),
+            Self::Baud115200 => 115200,
+            Self::Baud38400 => 38400,
+            Self::Baud19200 => 19200,
+            Self::Baud9600 => 9600,
         }
     }
 
-    // Get the inter-frame delay for the baud rate.
-    // See https://www.modbus.org/docs/Modbus_over_serial_line_V1_02.pdf
-    pub(crate) fn t35(&self) -> Duration {
+    /// Gets the baud rate for a rate in bits per second, if the motor supports it
+    pub fn from_rate(rate: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|b| b.rate() == rate)
+    }
+
+    // Get the value of the baud rate register for the baud rate
+    pub(crate) fn register_value(&self) -> u16 {
         match self {
-            Self::Baud115200 => Duration::from_micros(1750),
-            Self::Baud38400 => Duration::from_micros(1750),
-            Self::Baud19200 => Duration::from_micros(1979),
-            Self::Baud9600 => Duration::from_micros(3958),
+            Self::Baud115200 => 803,
+            Self::Baud38400 => 802,
+            Self::Baud19200 => 801,
+            Self::Baud9600 => 800,
+        }
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataBits {
+    Seven,
+    Eight,
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopBits {
+    One,
+    Two,
+}
+
+/// Character framing of a serial line, used to work out the Modbus RTU frame timing.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerialConfig {
+    /// Baud rate in bits per second
+    pub baud: u32,
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+}
+
+impl SerialConfig {
+    /// 8 data bits, no parity and 1 stop bit at the given baud rate
+    pub fn new(baud: u32) -> Self {
+        Self {
+            baud,
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+        }
+    }
+
+    /// Gets the number of bits used to send one character, including start, parity and stop bits
+    pub fn bits_per_char(&self) -> u32 {
+        let data = match self.data_bits {
+            DataBits::Seven => 7,
+            DataBits::Eight => 8,
+        };
+        let parity = match self.parity {
+            Parity::None => 0,
+            Parity::Even | Parity::Odd => 1,
+        };
+        let stop = match self.stop_bits {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+
+        1 + data + parity + stop
+    }
+
+    /// Gets the time taken to send one character
+    pub fn char_time(&self) -> Duration {
+        self.char_times(10)
+    }
+
+    /// Gets the maximum gap between characters of a frame (T1.5)
+    ///
+    /// Fixed at 750us above 19200 baud.
+    /// See <https://www.modbus.org/docs/Modbus_over_serial_line_V1_02.pdf>
+    pub fn t15(&self) -> Duration {
+        if self.baud > 19200 {
+            Duration::from_micros(750)
+        } else {
+            self.char_times(15)
+        }
+    }
+
+    /// Gets the minimum gap between frames (T3.5)
+    ///
+    /// Fixed at 1750us above 19200 baud.
+    /// See <https://www.modbus.org/docs/Modbus_over_serial_line_V1_02.pdf>
+    pub fn t35(&self) -> Duration {
+        if self.baud > 19200 {
+            Duration::from_micros(1750)
+        } else {
+            self.char_times(35)
         }
     }
+
+    /// Gets the baud rate as one supported by the motor, if it is one
+    pub fn rtu_baud(&self) -> Option<RtuBaud> {
+        RtuBaud::from_rate(self.baud)
+    }
+
+    // Duration of a number of tenths of a character, rounded up
+    fn char_times(&self, tenths: u64) -> Duration {
+        let bits = tenths * self.bits_per_char() as u64;
+        Duration::from_micros((bits * 100_000).div_ceil(self.baud.max(1) as u64))
+    }
 }
 
-#[derive(Debug, Format, Clone)]
+impl From<RtuBaud> for SerialConfig {
+    fn from(baud: RtuBaud) -> Self {
+        Self::new(baud.rate())
+    }
+}
+
+/// An alarm reported by the motor in its alarm code register (0x0E).
+///
+/// Codes without a variant are kept as [`AlarmCode::Unknown`] with their raw value, so a new or
+/// undocumented alarm is still reported as an alarm rather than as a communication error.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AlarmCode {
     PowerFailure,
     Overflow,
     Block,
     Overpressure,
+
+    /// An alarm code that is not documented
+    Unknown(u16),
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AlarmSeverity {
+    /// The motor stops, but can be recovered once the cause is removed
+    Error,
+
+    /// The motor stops, and the cause may have damaged the motor or the machine
+    Critical,
 }
 
-#[derive(Debug, Format, Clone)]
+impl AlarmCode {
+    pub(crate) fn from_register(value: u16) -> Option<Self> {
+        match value {
+            0 => None,
+            0x10 => Some(Self::PowerFailure),
+            0x12 => Some(Self::Overflow),
+            0x14 => Some(Self::Block),
+            0x15 => Some(Self::Overpressure),
+            v => Some(Self::Unknown(v)),
+        }
+    }
+
+    /// Gets the value of the alarm code register for this alarm
+    pub fn code(&self) -> u16 {
+        match self {
+            Self::PowerFailure => 0x10,
+            Self::Overflow => 0x12,
+            Self::Block => 0x14,
+            Self::Overpressure => 0x15,
+            Self::Unknown(v) => *v,
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::PowerFailure => "Power failure (supply undervoltage)",
+            Self::Overflow => "Overcurrent",
+            Self::Block => "Motor blocked (locked rotor)",
+            Self::Overpressure => "Supply overvoltage",
+            Self::Unknown(_) => "Unknown alarm",
+        }
+    }
+
+    pub fn severity(&self) -> AlarmSeverity {
+        match self {
+            Self::PowerFailure => AlarmSeverity::Error,
+            Self::Overflow => AlarmSeverity::Critical,
+            Self::Block => AlarmSeverity::Error,
+            Self::Overpressure => AlarmSeverity::Critical,
+            Self::Unknown(_) => AlarmSeverity::Critical,
+        }
+    }
+}
+
+/// Snapshot of the live status registers of a motor.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MotorStatus {
+    pub alarm: Option<AlarmCode>,
+
+    /// Current in A
+    pub current: f32,
+
+    /// Speed in RPM
+    pub speed: f32,
+
+    /// Supply voltage in V
+    pub voltage: f32,
+
+    /// Temperature in degrees C
+    pub temperature: u16,
+
+    pub pwm: u16,
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Direction {
     Clockwise,
     CounterClockwise,
 }
+
+/// How a motor is brought to a stop when it must not be left running.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopMode {
+    /// Set the target speed to zero, the drive remains enabled and holds position
+    ZeroSpeed,
+
+    /// Disable the drive, the motor coasts to a stop
+    DisableDrive,
+}
@@ -0,0 +1,238 @@
+use crate::SharedMotor;
+use core::cell::Cell;
+use embassy_futures::select::{Either, select};
+use embassy_sync::{
+    blocking_mutex::{self, raw::RawMutex},
+    signal::Signal,
+};
+use embassy_time::{Duration, Instant, Timer, with_timeout};
+
+#[derive(Clone, Copy)]
+struct State {
+    // Incremented every time the watchdog is armed, so that stale guards are ignored
+    generation: u32,
+    armed: bool,
+    tripped: bool,
+    stopped: bool,
+    window: Duration,
+    deadline: Instant,
+}
+
+/// Puts a motor into a safe state if the application stops communicating with it.
+///
+/// While armed (see [`Watchdog::arm`]), [`Watchdog::run`] sends a heartbeat to the motor at a fixed
+/// period.
+/// If the [`WatchdogGuard`] is not fed within its window, or is dropped without being disarmed,
+/// the target speed is set to zero and the drive is disabled.
+///
+/// The stop is sent through the [`SharedMotor`], so it cannot be sent while another task holds
+/// the motor, e.g. a hung task holding a [`SharedMotorGuard`](crate::SharedMotorGuard).
+/// The lock is only waited for up to one heartbeat period, then the stop is retried every period
+/// until it succeeds or the watchdog is armed again, see [`Watchdog::trip`].
+pub struct Watchdog<M: RawMutex> {
+    state: blocking_mutex::Mutex<M, Cell<State>>,
+    changed: Signal<M, ()>,
+}
+
+impl<M: RawMutex> Default for Watchdog<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M: RawMutex> Watchdog<M> {
+    pub const fn new() -> Self {
+        Self {
+            state: blocking_mutex::Mutex::new(Cell::new(State {
+                generation: 0,
+                armed: false,
+                tripped: false,
+                stopped: false,
+                window: Duration::from_ticks(0),
+                deadline: Instant::from_ticks(0),
+            })),
+            changed: Signal::new(),
+        }
+    }
+
+    /// Arms the watchdog, it must be fed through the returned guard at least once per window
+    pub fn arm(&self, window: Duration) -> WatchdogGuard<'_, M> {
+        self.update(|s| State {
+            generation: s.generation.wrapping_add(1),
+            armed: true,
+            tripped: false,
+            stopped: false,
+            window,
+            deadline: Instant::now() + window,
+        });
+
+        WatchdogGuard {
+            watchdog: self,
+            generation: self.state.lock(|s| s.get().generation),
+            disarmed: false,
+        }
+    }
+
+    /// If the watchdog tripped since it was last armed
+    pub fn tripped(&self) -> bool {
+        self.state.lock(|s| s.get().tripped)
+    }
+
+    /// Gets whether the motor was stopped, if the watchdog tripped since it was last armed
+    pub fn trip(&self) -> Option<WatchdogTrip> {
+        let state = self.state.lock(|s| s.get());
+        state.tripped.then_some(WatchdogTrip {
+            stopped: state.stopped,
+        })
+    }
+
+    /// Sends heartbeats and stops the motor when the watchdog trips, forever
+    ///
+    /// The motor is locked for at most `heartbeat` at a time, so a task holding it cannot block
+    /// the watchdog, but it does stop the heartbeat and the stop being sent.
+    pub async fn run<
+        MM: RawMutex,
+        I: embedded_io_async::Read + embedded_io_async::Write,
+        const N: usize,
+    >(
+        &self,
+        motor: &SharedMotor<MM, I, N>,
+        heartbeat: Duration,
+    ) -> ! {
+        let mut next_heartbeat = Instant::now();
+
+        loop {
+            let state = self.state.lock(|s| s.get());
+
+            if state.tripped && !state.stopped {
+                let generation = state.generation;
+                if Self::stop(motor, heartbeat).await {
+                    info!("Watchdog stopped the motor");
+                    self.update_generation(generation, |s| State { stopped: true, ..s });
+                } else if let Either::First(_) =
+                    select(self.changed.wait(), Timer::after(heartbeat)).await
+                {
+                    debug!("Watchdog state changed");
+                }
+                continue;
+            }
+
+            if !state.armed {
+                self.changed.wait().await;
+                next_heartbeat = Instant::now();
+                continue;
+            }
+
+            let now = Instant::now();
+
+            if now >= state.deadline {
+                error!("Watchdog tripped");
+                self.update(|s| State {
+                    armed: false,
+                    tripped: true,
+                    stopped: false,
+                    ..s
+                });
+            } else if now >= next_heartbeat {
+                debug!("Watchdog heartbeat");
+                match with_timeout(heartbeat, motor.lock()).await {
+                    Ok(mut motor) => {
+                        if let Err(e) = motor.modbus_enabled().await {
+                            warn!("Watchdog heartbeat failed: {}", e);
+                        }
+                    }
+                    Err(_) => warn!("Watchdog heartbeat skipped, motor is locked"),
+                }
+                next_heartbeat = now + heartbeat;
+            } else {
+                let wake = state.deadline.min(next_heartbeat);
+                if let Either::First(_) = select(self.changed.wait(), Timer::at(wake)).await {
+                    debug!("Watchdog state changed");
+                }
+            }
+        }
+    }
+
+    // Sets the target speed to zero and disables the drive, returning whether both succeeded
+    async fn stop<
+        MM: RawMutex,
+        I: embedded_io_async::Read + embedded_io_async::Write,
+        const N: usize,
+    >(
+        motor: &SharedMotor<MM, I, N>,
+        lock_timeout: Duration,
+    ) -> bool {
+        let Ok(mut motor) = with_timeout(lock_timeout, motor.lock()).await else {
+            error!("Watchdog could not stop the motor, it is locked by another task");
+            return false;
+        };
+
+        let zeroed = motor.set_target_rpm(0).await;
+        if let Err(e) = &zeroed {
+            warn!("Watchdog failed to zero target speed: {}", e);
+        }
+        let disabled = motor.set_drive_enabled(false).await;
+        if let Err(e) = &disabled {
+            warn!("Watchdog failed to disable drive: {}", e);
+        }
+
+        zeroed.is_ok() && disabled.is_ok()
+    }
+
+    fn update(&self, f: impl FnOnce(State) -> State) {
+        self.state.lock(|s| s.set(f(s.get())));
+        self.changed.signal(());
+    }
+
+    fn update_generation(&self, generation: u32, f: impl FnOnce(State) -> State) {
+        self.update(|s| if s.generation == generation { f(s) } else { s });
+    }
+}
+
+/// The outcome of a [`Watchdog`] tripping.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchdogTrip {
+    /// If the target speed was zeroed and the drive disabled
+    ///
+    /// `false` while the stop has not been sent yet, e.g. because another task holds the motor.
+    pub stopped: bool,
+}
+
+/// Keeps a [`Watchdog`] from tripping while it is regularly fed.
+///
+/// Dropping the guard trips the watchdog, use [`WatchdogGuard::disarm`] to stop it cleanly.
+pub struct WatchdogGuard<'a, M: RawMutex> {
+    watchdog: &'a Watchdog<M>,
+    generation: u32,
+    disarmed: bool,
+}
+
+impl<M: RawMutex> WatchdogGuard<'_, M> {
+    /// Restarts the window
+    pub fn feed(&self) {
+        self.watchdog.update_generation(self.generation, |s| State {
+            deadline: Instant::now() + s.window,
+            ..s
+        });
+    }
+
+    /// Stops the watchdog without tripping it
+    pub fn disarm(mut self) {
+        self.disarmed = true;
+        self.watchdog
+            .update_generation(self.generation, |s| State { armed: false, ..s });
+    }
+}
+
+impl<M: RawMutex> Drop for WatchdogGuard<'_, M> {
+    fn drop(&mut self) {
+        if !self.disarmed {
+            warn!("Watchdog guard dropped while armed");
+            self.watchdog.update_generation(self.generation, |s| State {
+                deadline: Instant::now(),
+                ..s
+            });
+        }
+    }
+}